@@ -1,4 +1,3 @@
-use hporecord::Record;
 use structopt::StructOpt;
 
 pub mod csv;
@@ -9,9 +8,9 @@ pub enum ConvertOpt {
 }
 
 impl ConvertOpt {
-    pub fn convert(&self, records: &[Record]) -> anyhow::Result<()> {
+    pub fn convert(&self) -> anyhow::Result<()> {
         match self {
-            Self::Csv(opt) => opt.convert(records),
+            Self::Csv(opt) => opt.convert(opt.input.read_records()?),
         }
     }
 }
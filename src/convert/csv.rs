@@ -1,85 +1,170 @@
-use crate::utils::build_id_mapping;
-use hporecord::{Record, StudyRecord};
-use std::collections::BTreeMap;
-use std::path::PathBuf;
+use crate::io::InputOpt;
+use crate::scale::ScaleOpt;
+use crate::utils::eval_study_script;
+use anyhow::{ensure, Context};
+use hporecord::{ParamDef, Record, StudyRecord};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
+/// Number of the rows buffered per table before they are appended to its file.
+///
+/// The files are not kept open so that many tables do not exhaust the file descriptors.
+const MAX_BUFFERED_ROWS: usize = 1024;
+
 #[derive(Debug, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 pub struct ConvertCsvOpt {
+    #[structopt(flatten)]
+    pub input: InputOpt,
+
     #[structopt(long)]
     pub table_name: String,
 
     #[structopt(long, default_value = "result/csv/")]
     pub out: PathBuf,
+
+    /// Log-transforms parameters in the same way as `importance` (see also `--log-param` etc).
+    #[structopt(long)]
+    pub log_scale: bool,
+
+    #[structopt(flatten)]
+    pub scale: ScaleOpt,
 }
 
 impl ConvertCsvOpt {
-    pub fn convert(&self, records: &[Record]) -> anyhow::Result<()> {
-        let id_mapping = build_id_mapping(&self.table_name, records)?;
+    pub fn convert<I>(&self, records: I) -> anyhow::Result<()>
+    where
+        I: Iterator<Item = anyhow::Result<Record>>,
+    {
+        ensure!(
+            self.log_scale || !self.scale.is_customized(),
+            "`--no-auto-log-scale`, `--log-param` and `--linear-param` require `--log-scale`"
+        );
+        std::fs::create_dir_all(&self.out)?;
 
         // TODO: Handle categorical
+        let lua = rlua::Lua::new();
+        let mut id_mapping = BTreeMap::new();
         let mut tables = BTreeMap::new();
+        let mut skipped_studies = BTreeSet::new();
         for record in records {
-            match record {
+            match record? {
                 Record::Study(study) => {
-                    let table_name = id_mapping.get(&study.id).unwrap_or(&study.id);
-                    if !tables.contains_key(table_name) {
-                        tables.insert(table_name.clone(), Table::new(study));
+                    let table_name = eval_study_script(&lua, &self.table_name, &study)?;
+                    if !tables.contains_key(&table_name) {
+                        let path = self.out.join(format!("{}.csv", table_name));
+                        let table = Table::new(&path, &study, self.log_scale_opt())?;
+                        tables.insert(table_name.clone(), table);
                     } else {
                         // TODO: Add validation
                     }
+                    id_mapping.insert(study.id, table_name);
                 }
                 Record::Eval(eval) => {
-                    let table_name = id_mapping.get(&eval.study).expect("TODO: warn and skip");
+                    let table_name = if let Some(table_name) = id_mapping.get(&eval.study) {
+                        table_name
+                    } else {
+                        if !skipped_studies.contains(&eval.study) {
+                            eprintln!("[WARN] Unknown study: {:?}", eval.study);
+                            skipped_studies.insert(eval.study);
+                        }
+                        continue;
+                    };
 
                     if !eval.state.is_complete() {
                         continue;
                     }
 
                     let table = tables.get_mut(table_name).expect("unreachable");
-                    let row = eval
-                        .params
-                        .iter()
-                        .chain(eval.values.iter())
-                        .copied()
-                        .collect();
-                    table.rows.push(row);
+                    let mut row = Vec::with_capacity(eval.params.len() + eval.values.len());
+                    for (&p, def) in eval.params.iter().zip(table.param_defs.iter()) {
+                        let p = if let Some(scale) = self.log_scale_opt() {
+                            scale
+                                .transform(def, p)
+                                .with_context(|| format!("study={:?}", eval.study))?
+                        } else {
+                            p
+                        };
+                        row.push(p.to_string());
+                    }
+                    row.extend(eval.values.iter().map(|v| v.to_string()));
+                    table.push(row)?;
                 }
             }
         }
 
-        std::fs::create_dir_all(&self.out)?;
-        for (name, table) in tables {
-            let path = self.out.join(format!("{}.csv", name));
-            let mut writer = csv::WriterBuilder::new().from_path(&path)?;
-            writer.write_record(&table.column_names)?;
-            for row in table.rows {
-                writer.write_record(&row.iter().map(|v| v.to_string()).collect::<Vec<_>>())?;
-            }
-            eprintln!("Generated: {:?}", path);
+        for table in tables.values_mut() {
+            table.flush()?;
+            eprintln!("Generated: {:?}", table.path);
         }
 
         Ok(())
     }
+
+    fn log_scale_opt(&self) -> Option<&ScaleOpt> {
+        if self.log_scale {
+            Some(&self.scale)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Table {
-    column_names: Vec<String>,
-    rows: Vec<Vec<f64>>,
+    path: PathBuf,
+    param_defs: Vec<ParamDef>,
+    rows: Vec<Vec<String>>,
 }
 
 impl Table {
-    fn new(record: &StudyRecord) -> Self {
-        Self {
-            column_names: record
+    fn new(path: &Path, record: &StudyRecord, scale: Option<&ScaleOpt>) -> anyhow::Result<Self> {
+        let mut writer = csv::WriterBuilder::new().from_path(path)?;
+        writer.write_record(
+            record
                 .params
                 .iter()
-                .map(|p| p.name.clone())
-                .chain(record.values.iter().map(|v| v.name.clone()))
-                .collect(),
+                .map(|p| {
+                    if matches!(scale, Some(scale) if scale.is_log_scale(p)) {
+                        format!("log({})", p.name)
+                    } else {
+                        p.name.clone()
+                    }
+                })
+                .chain(record.values.iter().map(|v| v.name.clone())),
+        )?;
+        writer.flush()?;
+        Ok(Self {
+            path: path.to_owned(),
+            param_defs: record.params.clone(),
             rows: Vec::new(),
+        })
+    }
+
+    fn push(&mut self, row: Vec<String>) -> anyhow::Result<()> {
+        self.rows.push(row);
+        if self.rows.len() >= MAX_BUFFERED_ROWS {
+            self.flush()?;
         }
+        Ok(())
+    }
+
+    /// Appends the buffered rows to the file.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("file={:?}", self.path))?;
+        let mut writer = csv::WriterBuilder::new().from_writer(file);
+        for row in self.rows.drain(..) {
+            writer.write_record(&row)?;
+        }
+        writer.flush()?;
+        Ok(())
     }
 }
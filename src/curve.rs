@@ -1,14 +1,28 @@
-use crate::utils::MeanAndStddev;
-use hporecord::{Record, ValueDef};
+use crate::io::InputOpt;
+use crate::parallel::ParallelOpt;
+use crate::utils::{eval_study_script, quantile, MeanAndStddev};
+use anyhow::{anyhow, ensure, Context};
+use hporecord::{Direction, Record, StudyRecord, ValueDef};
+use ordered_float::OrderedFloat;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 pub type LuaScript = String;
 pub type Studies = BTreeMap<String, BTreeMap<String, Study>>;
 
+/// Number of the grid points used if neither `--grid-step` nor `--grid-points` is specified.
+const DEFAULT_GRID_POINTS: usize = 100;
+
 #[derive(Debug, StructOpt)]
 pub struct CurveOpt {
+    #[structopt(flatten)]
+    pub input: InputOpt,
+
     #[structopt(long, default_value = "0")]
     pub span_index: usize,
 
@@ -20,52 +34,149 @@ pub struct CurveOpt {
 
     #[structopt(long)]
     pub optimizer_name: LuaScript,
-}
 
-impl CurveOpt {
-    pub fn calculate_optimization_curve(&self, records: &[Record]) -> anyhow::Result<Studies> {
-        let studies = self.build_studies(records)?;
-        Ok(studies)
-    }
+    /// Interval of the grid points onto which the best-so-far curves are resampled.
+    ///
+    /// The grid points are the multiples of this value within the range of the span ends of each problem.
+    #[structopt(long, conflicts_with_all = &["grid-points", "log-grid"])]
+    pub grid_step: Option<f64>,
 
-    fn build_studies(&self, records: &[Record]) -> anyhow::Result<Studies> {
-        let mut problem_mapping = BTreeMap::new();
-        let mut optimizer_mapping = BTreeMap::new();
-        for record in records {
-            if let Record::Study(study) = record {
-                let lua = rlua::Lua::new();
-                let new_id: String = lua.context(|lua_ctx| {
-                    let globals = lua_ctx.globals();
+    /// Number of the grid points spanning the range of the span ends of each problem (default: 100).
+    #[structopt(long)]
+    pub grid_points: Option<NonZeroUsize>,
+
+    /// Spaces the grid points logarithmically.
+    #[structopt(long)]
+    pub log_grid: bool,
 
-                    // TODO
-                    globals.set("attrs", study.attrs.clone())?;
+    /// Statistic summarizing the best-so-far values of the seeds at each grid point.
+    #[structopt(long, default_value = "mean-stddev", possible_values = CurveStatistic::NAMES)]
+    pub statistic: CurveStatistic,
 
-                    lua_ctx.load(&self.problem_name).eval()
-                })?;
-                problem_mapping.insert(&study.id, new_id);
-            }
+    /// Quantile of the lower bound of the band (`--statistic quantiles` only).
+    #[structopt(long, default_value = "0.1")]
+    pub lower_quantile: f64,
 
-            if let Record::Study(study) = record {
-                let lua = rlua::Lua::new();
-                let new_id: String = lua.context(|lua_ctx| {
-                    let globals = lua_ctx.globals();
+    /// Quantile of the upper bound of the band (`--statistic quantiles` only).
+    #[structopt(long, default_value = "0.9")]
+    pub upper_quantile: f64,
 
-                    // TODO
-                    globals.set("attrs", study.attrs.clone())?;
+    /// Number of bootstrap replicates (`--statistic bootstrap-ci` only).
+    #[structopt(long, default_value = "1000")]
+    pub bootstrap: NonZeroUsize,
 
-                    lua_ctx.load(&self.optimizer_name).eval()
-                })?;
-                optimizer_mapping.insert(&study.id, new_id);
-            }
+    /// Confidence level of the bootstrap confidence intervals.
+    #[structopt(long, default_value = "0.95")]
+    pub confidence: f64,
+
+    /// Random seed used for bootstrapping.
+    #[structopt(long, default_value = "0")]
+    pub seed: u64,
+
+    /// How to aggregate the seeds of an optimizer which end at different span values.
+    #[structopt(long, default_value = "truncate", possible_values = RaggedPolicy::NAMES)]
+    pub ragged: RaggedPolicy,
+
+    /// Known optimal value of the objective shared by all the problems.
+    #[structopt(long, conflicts_with_all = &["optimum-script", "optimum-file"])]
+    pub optimum_value: Option<f64>,
+
+    /// Lua expression over `attrs` which returns the optimal value of the problem of a study.
+    #[structopt(long, conflicts_with = "optimum-file")]
+    pub optimum_script: Option<LuaScript>,
+
+    /// JSON file which maps problem names to their optimal values.
+    #[structopt(long)]
+    pub optimum_file: Option<PathBuf>,
+
+    /// Transformation of the best values using the optimum (the transformed curves are minimized).
+    #[structopt(long, default_value = "raw", possible_values = CurveTransform::NAMES)]
+    pub transform: CurveTransform,
+
+    /// Lower bound of the regrets before taking logarithms (`--transform log-regret` only).
+    #[structopt(long, default_value = "1e-12")]
+    pub log_regret_floor: f64,
+
+    #[structopt(flatten)]
+    pub parallel: ParallelOpt,
+}
+
+impl CurveOpt {
+    pub fn calculate_optimization_curve<I>(&self, records: I) -> anyhow::Result<Studies>
+    where
+        I: Iterator<Item = anyhow::Result<Record>>,
+    {
+        if let Some(step) = self.grid_step {
+            ensure!(step > 0.0, "the grid step must be positive");
         }
+        ensure!(
+            0.0 <= self.lower_quantile
+                && self.lower_quantile <= self.upper_quantile
+                && self.upper_quantile <= 1.0,
+            "the quantiles must satisfy 0 <= lower <= upper <= 1"
+        );
+        ensure!(
+            self.confidence > 0.0 && self.confidence < 1.0,
+            "the confidence level must be in the range (0, 1)"
+        );
+        ensure!(
+            self.transform == CurveTransform::Raw
+                || self.optimum_value.is_some()
+                || self.optimum_script.is_some()
+                || self.optimum_file.is_some(),
+            "`--transform {}` requires the optimum (`--optimum-value`, `--optimum-script` or `--optimum-file`)",
+            self.transform.name()
+        );
+        ensure!(
+            self.log_regret_floor > 0.0,
+            "the log regret floor must be positive"
+        );
+        let studies = self.build_studies(records)?;
+        Ok(studies)
+    }
 
+    fn build_studies<I>(&self, records: I) -> anyhow::Result<Studies>
+    where
+        I: Iterator<Item = anyhow::Result<Record>>,
+    {
+        let lua = rlua::Lua::new();
+        let optimum_file = self.read_optimum_file()?;
+        let mut optima = BTreeMap::new();
+        let mut id_mapping = BTreeMap::new();
         let mut studies: Studies = BTreeMap::new();
         let mut skipped_studies = BTreeSet::new();
         for record in records {
-            match record {
+            match record? {
                 Record::Study(study) => {
-                    let problem_id = problem_mapping.get(&study.id).expect("unreachable");
-                    let optimizer_id = optimizer_mapping.get(&study.id).expect("unreachable");
+                    ensure!(
+                        self.span_index < study.spans.len(),
+                        "the span index {} is out of range for the study {:?} (must be less than {})",
+                        self.span_index,
+                        study.id,
+                        study.spans.len()
+                    );
+                    ensure!(
+                        self.objective_index < study.values.len(),
+                        "the objective index {} is out of range for the study {:?} (must be less than {})",
+                        self.objective_index,
+                        study.id,
+                        study.values.len()
+                    );
+
+                    let problem_id = eval_study_script(&lua, &self.problem_name, &study)?;
+                    let optimizer_id = eval_study_script(&lua, &self.optimizer_name, &study)?;
+                    if let Some(optimum) =
+                        self.optimum(&lua, optimum_file.as_ref(), &problem_id, &study)?
+                    {
+                        let expected = *optima.entry(problem_id.clone()).or_insert(optimum);
+                        ensure!(
+                            expected == optimum,
+                            "the problem {:?} has different optima ({} and {})",
+                            problem_id,
+                            expected,
+                            optimum
+                        );
+                    }
                     studies
                         .entry(problem_id.clone())
                         .or_default()
@@ -73,108 +184,773 @@ impl CurveOpt {
                         .or_insert_with(|| Study {
                             span_name: study.spans[self.span_index].name.clone(),
                             objective: study.values[self.objective_index].clone(),
+                            xs: Vec::new(),
                             best_values_avg: MeanAndStddev {
                                 mean: Vec::new(),
                                 stddev: Vec::new(),
                             },
-                            best_values: Default::default(),
+                            band: None,
+                            sample_counts: Vec::new(),
+                            optimum: None,
+                            curves: Default::default(),
                             samples: 0,
                         })
-                        .best_values
-                        .insert(study.id.clone(), vec![None]);
+                        .curves
+                        .insert(study.id.clone(), StepCurve::default());
+                    id_mapping.insert(study.id, (problem_id, optimizer_id));
                 }
                 Record::Eval(eval) => {
                     if !eval.state.is_complete() {
                         continue;
                     }
-                    if !problem_mapping.contains_key(&eval.study) {
+                    if !id_mapping.contains_key(&eval.study) {
                         if !skipped_studies.contains(&eval.study) {
                             eprintln!("[WARN] Unknown study: {:?}", eval.study);
-                            skipped_studies.insert(&eval.study);
+                            skipped_studies.insert(eval.study);
                         }
                         continue;
                     }
 
-                    let problem_id = problem_mapping.get(&eval.study).expect("TODO");
-                    let optimizer_id = optimizer_mapping.get(&eval.study).expect("TODO");
+                    let (problem_id, optimizer_id) =
+                        id_mapping.get(&eval.study).expect("unreachable");
 
                     let study = studies
                         .get_mut(problem_id)
                         .expect("unreachable")
                         .get_mut(optimizer_id)
                         .expect("unreachable");
-                    let best_values = study.best_values.get_mut(&eval.study).expect("TODO");
+                    let curve = study.curves.get_mut(&eval.study).expect("unreachable");
 
                     let span = eval.spans[self.span_index];
                     let value = eval.values[self.objective_index];
-                    let current = span.end.round() as usize;
-                    while best_values.len() <= current {
-                        // TODO: optimize
-                        best_values.push(best_values[best_values.len() - 1]);
-                    }
-
-                    for v in &mut best_values[current..] {
-                        if let Some(v) = v {
-                            *v = study.objective.direction.better(*v, value);
-                            if *v != value {
-                                break;
-                            }
-                        } else {
-                            *v = Some(value);
-                        }
-                    }
+                    curve.update(study.objective.direction, span.end, value);
                 }
             }
         }
 
-        for studies in studies.values_mut() {
+        for (problem_id, studies) in studies.iter_mut() {
+            let optimum = optima.get(problem_id).copied();
             for study in studies.values_mut() {
-                study.samples = study.best_values.len();
+                study.optimum = optimum;
+            }
+            if let Some(optimum) = optimum {
+                self.transform_curves(problem_id, studies, optimum);
+            }
+        }
 
-                let size = study
-                    .best_values
+        // The optimizers of a problem share the same grid so that their curves can be compared.
+        let mut targets = Vec::new();
+        for (problem_id, studies) in studies.iter_mut() {
+            let curves = || studies.values().flat_map(|s| s.curves.values());
+            let start = curves().filter_map(|c| c.start).map(OrderedFloat).min();
+            let end = curves().filter_map(|c| c.end).map(OrderedFloat).max();
+            let grid = if let (Some(start), Some(end)) = (start, end) {
+                self.grid(start.0, end.0)
+                    .with_context(|| format!("problem={:?}", problem_id))?
+            } else {
+                Vec::new()
+            };
+            for study in studies.values_mut() {
+                let ends = study
+                    .curves
                     .values()
-                    .map(|vs| vs.len())
-                    .min()
-                    .expect("unreachable");
-                for i in 0..size {
-                    if study.best_values.values().any(|vs| vs[i].is_none()) {
-                        study.best_values_avg.mean.push(None);
-                        study.best_values_avg.stddev.push(None);
-                        continue;
-                    }
-
-                    let total = study
-                        .best_values
-                        .values()
-                        .map(|vs| vs[i].expect("unreachable"))
-                        .sum::<f64>();
-                    let mean = total / study.best_values.len() as f64;
-                    let stddev = (study
-                        .best_values
-                        .values()
-                        .map(|vs| (vs[i].expect("unreachable") - mean).powi(2))
-                        .sum::<f64>()
-                        / study.best_values.len() as f64)
-                        .sqrt();
-                    study.best_values_avg.mean.push(Some(mean));
-                    study.best_values_avg.stddev.push(Some(stddev));
+                    .map(|c| OrderedFloat(c.end.unwrap_or(f64::NEG_INFINITY)));
+                let end = match self.ragged {
+                    RaggedPolicy::Truncate => ends.min(),
+                    RaggedPolicy::CarryForward | RaggedPolicy::Survivors => ends.max(),
                 }
+                .expect("unreachable")
+                .0;
+                let xs = grid
+                    .iter()
+                    .copied()
+                    .take_while(|&x| x <= end)
+                    .collect::<Vec<_>>();
+                targets.push((study, xs));
             }
         }
 
+        let aggregates = self.parallel.map(&targets, |(study, xs)| {
+            self.aggregate_best_values(study, xs)
+        });
+        for ((study, xs), (average, band, counts)) in targets.into_iter().zip(aggregates) {
+            study.samples = study.curves.len();
+            study.xs = xs;
+            study.best_values_avg = average;
+            study.band = Some(band);
+            study.sample_counts = counts;
+        }
+
         Ok(studies)
     }
+
+    fn read_optimum_file(&self) -> anyhow::Result<Option<BTreeMap<String, f64>>> {
+        if let Some(path) = &self.optimum_file {
+            let file = std::fs::File::open(path).with_context(|| format!("file={:?}", path))?;
+            let optima = serde_json::from_reader(std::io::BufReader::new(file))
+                .with_context(|| format!("file={:?}", path))?;
+            Ok(Some(optima))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn optimum(
+        &self,
+        lua: &rlua::Lua,
+        optimum_file: Option<&BTreeMap<String, f64>>,
+        problem_id: &str,
+        study: &StudyRecord,
+    ) -> anyhow::Result<Option<f64>> {
+        if let Some(optimum) = self.optimum_value {
+            Ok(Some(optimum))
+        } else if let Some(script) = &self.optimum_script {
+            let optimum = eval_optimum_script(lua, script, study)
+                .with_context(|| format!("study={:?}", study.id))?;
+            Ok(Some(optimum))
+        } else if let Some(optima) = optimum_file {
+            let optimum = optima.get(problem_id).with_context(|| {
+                format!("the optimum of the problem {:?} is not found", problem_id)
+            })?;
+            Ok(Some(*optimum))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Transforms the curves of all the optimizers of a problem by `--transform`.
+    fn transform_curves(
+        &self,
+        problem_id: &str,
+        studies: &mut BTreeMap<String, Study>,
+        optimum: f64,
+    ) {
+        if self.transform == CurveTransform::Raw {
+            return;
+        }
+
+        let curves = studies
+            .values_mut()
+            .flat_map(|study| {
+                let direction = study.objective.direction;
+                study.curves.values_mut().map(move |c| (direction, c))
+            })
+            .collect();
+        if self.transform_regrets(curves, optimum) {
+            eprintln!(
+                "[WARN] Some values of the problem {:?} are better than the optimum {} (their regrets are clamped to 0)",
+                problem_id, optimum
+            );
+        }
+
+        for study in studies.values_mut() {
+            study.objective.direction = Direction::Minimize;
+            study.objective.name = format!("{} ({})", study.objective.name, self.transform.name());
+        }
+    }
+
+    /// Replaces the best values of the `curves` of a problem by their transformed regrets.
+    ///
+    /// Returns `true` if some values are better than `optimum` (their regrets are clamped to 0).
+    fn transform_regrets(&self, curves: Vec<(Direction, &mut StepCurve)>, optimum: f64) -> bool {
+        let regret = |direction: Direction, v: f64| match direction {
+            Direction::Minimize => v - optimum,
+            Direction::Maximize => optimum - v,
+        };
+
+        // The reference of `normalized` is common to all the seeds of the problem so that the
+        // curves of different optimizers remain comparable.
+        let reference = curves
+            .iter()
+            .filter_map(|(direction, c)| c.points.first().map(|&(_, v)| regret(*direction, v)))
+            .fold(0.0, f64::max);
+
+        let mut clamped = false;
+        for (direction, curve) in curves {
+            for (_, v) in &mut curve.points {
+                let mut r = regret(direction, *v);
+                if r < 0.0 {
+                    clamped = true;
+                    r = 0.0;
+                }
+                *v = match self.transform {
+                    CurveTransform::Raw => *v,
+                    CurveTransform::SimpleRegret => r,
+                    CurveTransform::LogRegret => r.max(self.log_regret_floor).log10(),
+                    CurveTransform::Normalized if reference > 0.0 => r / reference,
+                    CurveTransform::Normalized => 0.0,
+                };
+            }
+        }
+        clamped
+    }
+
+    fn aggregate_best_values(
+        &self,
+        study: &Study,
+        xs: &[f64],
+    ) -> (MeanAndStddev<Vec<Option<f64>>>, Band, Vec<usize>) {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut average = MeanAndStddev {
+            mean: Vec::new(),
+            stddev: Vec::new(),
+        };
+        let mut band = Band {
+            statistic: self.statistic,
+            label: self.statistic_label(),
+            center: Vec::new(),
+            lower: Vec::new(),
+            upper: Vec::new(),
+        };
+        let mut counts = Vec::new();
+        for &x in xs {
+            let values = study
+                .curves
+                .values()
+                .filter(|c| {
+                    // Seeds which have ended before `x` carry their last best values forward unless
+                    // only the surviving ones are aggregated.
+                    self.ragged != RaggedPolicy::Survivors || matches!(c.end, Some(end) if x <= end)
+                })
+                .map(|c| c.value_at(x))
+                .collect::<Option<Vec<_>>>()
+                .filter(|values| !values.is_empty());
+            counts.push(values.as_ref().map_or(0, |values| values.len()));
+            if let Some(mut values) = values {
+                let MeanAndStddev { mean, stddev } = MeanAndStddev::from_samples(&values);
+                average.mean.push(Some(mean));
+                average.stddev.push(Some(stddev));
+
+                let (center, lower, upper) = self.summarize(&mut values, &mut rng);
+                band.center.push(Some(center));
+                band.lower.push(Some(lower));
+                band.upper.push(Some(upper));
+            } else {
+                average.mean.push(None);
+                average.stddev.push(None);
+                band.center.push(None);
+                band.lower.push(None);
+                band.upper.push(None);
+            }
+        }
+        (average, band, counts)
+    }
+
+    /// Returns the center, the lower bound and the upper bound of `values` by the chosen statistic.
+    fn summarize<R: Rng>(&self, values: &mut [f64], rng: &mut R) -> (f64, f64, f64) {
+        values.sort_by_key(|&v| OrderedFloat(v));
+        let n = values.len();
+        let MeanAndStddev { mean, stddev } = MeanAndStddev::from_samples(values);
+        match self.statistic {
+            CurveStatistic::MeanStddev => (mean, mean - stddev, mean + stddev),
+            CurveStatistic::MeanStderr => {
+                // `stddev` is the population one, so this equals the sample one divided by `sqrt(n)`.
+                let stderr = stddev / ((n.max(2) - 1) as f64).sqrt();
+                (mean, mean - stderr, mean + stderr)
+            }
+            CurveStatistic::MedianIqr => (
+                quantile(values, 0.5),
+                quantile(values, 0.25),
+                quantile(values, 0.75),
+            ),
+            CurveStatistic::Quantiles => (
+                quantile(values, 0.5),
+                quantile(values, self.lower_quantile),
+                quantile(values, self.upper_quantile),
+            ),
+            CurveStatistic::BootstrapCi => {
+                let mut means = (0..self.bootstrap.get())
+                    .map(|_| (0..n).map(|_| values[rng.gen_range(0, n)]).sum::<f64>() / n as f64)
+                    .collect::<Vec<_>>();
+                means.sort_by_key(|&m| OrderedFloat(m));
+                let alpha = (1.0 - self.confidence) / 2.0;
+                (mean, quantile(&means, alpha), quantile(&means, 1.0 - alpha))
+            }
+        }
+    }
+
+    fn statistic_label(&self) -> String {
+        match self.statistic {
+            CurveStatistic::MeanStddev => "mean ± stddev".to_owned(),
+            CurveStatistic::MeanStderr => "mean ± stderr".to_owned(),
+            CurveStatistic::MedianIqr => "median (IQR)".to_owned(),
+            CurveStatistic::Quantiles => format!(
+                "median ({}-{} quantiles)",
+                self.lower_quantile, self.upper_quantile
+            ),
+            CurveStatistic::BootstrapCi => {
+                format!("mean ({}% bootstrap CI)", self.confidence * 100.0)
+            }
+        }
+    }
+
+    /// Returns the grid points for the span ends in `[start, end]`.
+    fn grid(&self, start: f64, end: f64) -> anyhow::Result<Vec<f64>> {
+        if self.log_grid {
+            ensure!(
+                start > 0.0,
+                "the log grid requires positive span ends (the smallest is {})",
+                start
+            );
+            let n = self.grid_points.map_or(DEFAULT_GRID_POINTS, |n| n.get());
+            let mut xs = linspace(start.ln(), end.ln(), n)
+                .into_iter()
+                .map(f64::exp)
+                .collect::<Vec<_>>();
+
+            // Avoids rounding errors at both ends so that no curve is truncated by them.
+            xs[0] = start;
+            if n > 1 {
+                xs[n - 1] = end;
+            }
+            return Ok(xs);
+        }
+        let step = if let Some(step) = self.grid_step {
+            step
+        } else {
+            let n = self.grid_points.map_or(DEFAULT_GRID_POINTS, |n| n.get());
+            return Ok(linspace(start, end, n));
+        };
+        let (first, last) = ((start / step).ceil(), (end / step).floor());
+        if last < first {
+            return Ok(Vec::new());
+        }
+        let n = (last - first) as usize + 1;
+        Ok((0..n).map(|i| (first + i as f64) * step).collect())
+    }
+}
+
+fn eval_optimum_script(lua: &rlua::Lua, script: &str, study: &StudyRecord) -> anyhow::Result<f64> {
+    let optimum = lua.context(|lua_ctx| {
+        lua_ctx.globals().set("attrs", study.attrs.clone())?;
+        lua_ctx.load(script).eval()
+    })?;
+    Ok(optimum)
+}
+
+/// Returns `n` evenly spaced points from `start` to `end` (inclusive).
+fn linspace(start: f64, end: f64, n: usize) -> Vec<f64> {
+    if n == 1 {
+        return vec![start];
+    }
+    (0..n)
+        .map(|i| {
+            if i == n - 1 {
+                end
+            } else {
+                start + (end - start) * i as f64 / (n - 1) as f64
+            }
+        })
+        .collect()
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Study {
     pub span_name: String,
     pub objective: ValueDef,
+
+    /// Grid points of the span at which the curves are resampled.
+    #[serde(default)]
+    pub xs: Vec<f64>,
+
     pub best_values_avg: MeanAndStddev<Vec<Option<f64>>>,
+
+    /// Best values summarized by the chosen statistic (absent in the outputs of older versions).
+    #[serde(default)]
+    pub band: Option<Band>,
+
     pub samples: usize,
 
-    // TODO: delete
-    #[serde(skip_serializing, default)]
-    best_values: BTreeMap<String, Vec<Option<f64>>>,
+    /// Number of the seeds aggregated at each grid point (absent in the outputs of older versions).
+    #[serde(default)]
+    pub sample_counts: Vec<usize>,
+
+    /// Known optimal value of the objective, if given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub optimum: Option<f64>,
+
+    #[serde(skip, default)]
+    curves: BTreeMap<String, StepCurve>,
+}
+
+/// Transformation of the best values using the optimum of a problem.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CurveTransform {
+    /// Raw best values.
+    #[default]
+    Raw,
+
+    /// Distance from the optimum.
+    SimpleRegret,
+
+    /// Base-10 logarithm of the simple regret.
+    LogRegret,
+
+    /// Simple regret divided by the largest initial regret over all the seeds of each problem (i.e., at most 1 and 0 at the optimum).
+    Normalized,
+}
+
+impl CurveTransform {
+    pub const NAMES: &'static [&'static str] =
+        &["raw", "simple-regret", "log-regret", "normalized"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Raw => "raw",
+            Self::SimpleRegret => "simple-regret",
+            Self::LogRegret => "log-regret",
+            Self::Normalized => "normalized",
+        }
+    }
+}
+
+impl std::str::FromStr for CurveTransform {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(Self::Raw),
+            "simple-regret" => Ok(Self::SimpleRegret),
+            "log-regret" => Ok(Self::LogRegret),
+            "normalized" => Ok(Self::Normalized),
+            _ => Err(anyhow!("unknown transform {:?}", s)),
+        }
+    }
+}
+
+/// Policy for aggregating seeds which end at different span values.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RaggedPolicy {
+    /// Truncates the curve of an optimizer to the end of its shortest seed.
+    #[default]
+    Truncate,
+
+    /// Extends the curve to the end of the longest seed, carrying the last best values of the ended seeds forward.
+    CarryForward,
+
+    /// Extends the curve to the end of the longest seed, aggregating only the seeds which have not ended yet.
+    Survivors,
+}
+
+impl RaggedPolicy {
+    pub const NAMES: &'static [&'static str] = &["truncate", "carry-forward", "survivors"];
+}
+
+impl std::str::FromStr for RaggedPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "truncate" => Ok(Self::Truncate),
+            "carry-forward" => Ok(Self::CarryForward),
+            "survivors" => Ok(Self::Survivors),
+            _ => Err(anyhow!("unknown ragged policy {:?}", s)),
+        }
+    }
+}
+
+/// Central values and bands of the best values at the grid points.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Band {
+    pub statistic: CurveStatistic,
+
+    /// Human-readable description of the statistic (e.g., "median (IQR)").
+    pub label: String,
+
+    pub center: Vec<Option<f64>>,
+    pub lower: Vec<Option<f64>>,
+    pub upper: Vec<Option<f64>>,
+}
+
+impl Band {
+    /// Makes the mean ± stddev band from the outputs of older versions.
+    pub fn from_mean_and_stddev(average: &MeanAndStddev<Vec<Option<f64>>>) -> Self {
+        let bound = |sign: f64| {
+            average
+                .mean
+                .iter()
+                .zip(average.stddev.iter())
+                .map(|(&m, &s)| Some(m? + sign * s?))
+                .collect()
+        };
+        Self {
+            statistic: CurveStatistic::MeanStddev,
+            label: "mean ± stddev".to_owned(),
+            center: average.mean.clone(),
+            lower: bound(-1.0),
+            upper: bound(1.0),
+        }
+    }
+}
+
+/// Statistic summarizing the best-so-far values of the seeds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CurveStatistic {
+    /// Mean with the band of the (population) standard deviation.
+    #[default]
+    MeanStddev,
+
+    /// Mean with the band of the standard error.
+    MeanStderr,
+
+    /// Median with the band of the interquartile range.
+    MedianIqr,
+
+    /// Median with the band between `--lower-quantile` and `--upper-quantile`.
+    Quantiles,
+
+    /// Mean with the band of the percentile bootstrap confidence interval.
+    BootstrapCi,
+}
+
+impl CurveStatistic {
+    pub const NAMES: &'static [&'static str] = &[
+        "mean-stddev",
+        "mean-stderr",
+        "median-iqr",
+        "quantiles",
+        "bootstrap-ci",
+    ];
+}
+
+impl std::str::FromStr for CurveStatistic {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mean-stddev" => Ok(Self::MeanStddev),
+            "mean-stderr" => Ok(Self::MeanStderr),
+            "median-iqr" => Ok(Self::MedianIqr),
+            "quantiles" => Ok(Self::Quantiles),
+            "bootstrap-ci" => Ok(Self::BootstrapCi),
+            _ => Err(anyhow!("unknown statistic {:?}", s)),
+        }
+    }
+}
+
+/// Best-so-far values of a study, represented by the change points of the step function.
+#[derive(Debug, Default, Clone)]
+struct StepCurve {
+    /// `(x, best)` pairs in ascending order of `x`, where each `best` improves on the previous one.
+    points: Vec<(f64, f64)>,
+
+    /// Smallest span end observed.
+    start: Option<f64>,
+
+    /// Largest span end observed.
+    end: Option<f64>,
+}
+
+impl StepCurve {
+    fn update(&mut self, direction: Direction, x: f64, value: f64) {
+        self.start = Some(self.start.map_or(x, |start| start.min(x)));
+        self.end = Some(self.end.map_or(x, |end| end.max(x)));
+        if value.is_nan() {
+            return;
+        }
+
+        let mut i = self.points.partition_point(|&(px, _)| px <= x);
+        if i > 0 {
+            let (px, best) = self.points[i - 1];
+            if direction.better(best, value) == best {
+                return;
+            }
+            if px == x {
+                i -= 1;
+            }
+        }
+
+        // Removes the subsequent points which do not improve on `value` anymore.
+        let mut j = i;
+        while j < self.points.len() && direction.better(self.points[j].1, value) == value {
+            j += 1;
+        }
+        self.points.splice(i..j, std::iter::once((x, value)));
+    }
+
+    fn value_at(&self, x: f64) -> Option<f64> {
+        let i = self.points.partition_point(|&(px, _)| px <= x);
+        if i == 0 {
+            None
+        } else {
+            Some(self.points[i - 1].1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve(evals: &[(f64, f64)]) -> StepCurve {
+        let mut curve = StepCurve::default();
+        for &(x, value) in evals {
+            curve.update(Direction::Minimize, x, value);
+        }
+        curve
+    }
+
+    fn opt(args: &[&str]) -> CurveOpt {
+        let mut argv = vec!["curve", "--problem-name", "p", "--optimizer-name", "o"];
+        argv.extend_from_slice(args);
+        CurveOpt::from_iter(argv)
+    }
+
+    fn transform(
+        transform: &str,
+        direction: Direction,
+        evals: &[(f64, f64)],
+        optimum: f64,
+    ) -> (Vec<(f64, f64)>, bool) {
+        let mut curve = StepCurve::default();
+        for &(x, value) in evals {
+            curve.update(direction, x, value);
+        }
+        let clamped = opt(&["--transform", transform])
+            .transform_regrets(vec![(direction, &mut curve)], optimum);
+        (curve.points, clamped)
+    }
+
+    #[test]
+    fn step_curve_works() {
+        let curve = curve(&[(1.0, 5.0), (2.0, 6.0), (3.0, 4.0)]);
+        assert_eq!(curve.points, vec![(1.0, 5.0), (3.0, 4.0)]);
+        assert_eq!(curve.value_at(0.5), None);
+        assert_eq!(curve.value_at(1.0), Some(5.0));
+        assert_eq!(curve.value_at(2.5), Some(5.0));
+        assert_eq!(curve.value_at(3.0), Some(4.0));
+        assert_eq!(curve.value_at(10.0), Some(4.0));
+        assert_eq!((curve.start, curve.end), (Some(1.0), Some(3.0)));
+    }
+
+    #[test]
+    fn step_curve_accepts_out_of_order_evals() {
+        let curve = curve(&[(3.0, 4.0), (1.0, 5.0), (2.0, 3.0), (0.5, 6.0)]);
+        assert_eq!(curve.points, vec![(0.5, 6.0), (1.0, 5.0), (2.0, 3.0)]);
+        assert_eq!((curve.start, curve.end), (Some(0.5), Some(3.0)));
+    }
+
+    #[test]
+    fn step_curve_replaces_points_at_equal_x() {
+        let curve = curve(&[(1.0, 5.0), (2.0, 4.0), (2.0, 3.0), (2.0, 3.5)]);
+        assert_eq!(curve.points, vec![(1.0, 5.0), (2.0, 3.0)]);
+    }
+
+    #[test]
+    fn step_curve_removes_points_not_improving_anymore() {
+        let curve = curve(&[(2.0, 5.0), (3.0, 4.0), (4.0, 2.0), (1.0, 4.0)]);
+        assert_eq!(curve.points, vec![(1.0, 4.0), (4.0, 2.0)]);
+
+        let mut curve = StepCurve::default();
+        for &(x, value) in &[(2.0, 1.0), (3.0, 2.0), (1.0, 3.0)] {
+            curve.update(Direction::Maximize, x, value);
+        }
+        assert_eq!(curve.points, vec![(1.0, 3.0)]);
+    }
+
+    #[test]
+    fn step_curve_ignores_nan_values() {
+        let curve = curve(&[(1.0, f64::NAN), (2.0, 5.0), (3.0, f64::NAN)]);
+        assert_eq!(curve.points, vec![(2.0, 5.0)]);
+        assert_eq!(curve.value_at(1.5), None);
+        assert_eq!((curve.start, curve.end), (Some(1.0), Some(3.0)));
+    }
+
+    #[test]
+    fn simple_regret_follows_direction() {
+        let evals = [(1.0, 5.0), (2.0, 3.0)];
+        assert_eq!(
+            transform("simple-regret", Direction::Minimize, &evals, 1.0),
+            (vec![(1.0, 4.0), (2.0, 2.0)], false)
+        );
+
+        let evals = [(1.0, 0.5), (2.0, 0.75)];
+        assert_eq!(
+            transform("simple-regret", Direction::Maximize, &evals, 1.0),
+            (vec![(1.0, 0.5), (2.0, 0.25)], false)
+        );
+    }
+
+    #[test]
+    fn regrets_better_than_optimum_are_clamped() {
+        let evals = [(1.0, 2.0), (2.0, 0.5)];
+        assert_eq!(
+            transform("simple-regret", Direction::Minimize, &evals, 1.0),
+            (vec![(1.0, 1.0), (2.0, 0.0)], true)
+        );
+        assert_eq!(
+            transform("log-regret", Direction::Maximize, &evals, 1.0),
+            (vec![(1.0, -12.0)], true)
+        );
+        assert_eq!(
+            transform("log-regret", Direction::Minimize, &[(1.0, 101.0)], 1.0),
+            (vec![(1.0, 2.0)], false)
+        );
+    }
+
+    #[test]
+    fn normalized_regrets_share_reference_within_problem() {
+        let mut a = curve(&[(1.0, 0.5), (2.0, 0.25)]);
+        let mut b = StepCurve::default();
+        b.update(Direction::Maximize, 1.0, -8.0);
+        b.update(Direction::Maximize, 2.0, -1.0);
+
+        let clamped = opt(&["--transform", "normalized"]).transform_regrets(
+            vec![(Direction::Minimize, &mut a), (Direction::Maximize, &mut b)],
+            0.0,
+        );
+        assert!(!clamped);
+        assert_eq!(a.points, vec![(1.0, 0.0625), (2.0, 0.03125)]);
+        assert_eq!(b.points, vec![(1.0, 1.0), (2.0, 0.125)]);
+    }
+
+    #[test]
+    fn linspace_works() {
+        assert_eq!(linspace(0.0, 1.0, 5), vec![0.0, 0.25, 0.5, 0.75, 1.0]);
+        assert_eq!(linspace(2.0, 3.0, 1), vec![2.0]);
+        assert_eq!(linspace(0.1, 0.7, 3).last(), Some(&0.7));
+    }
+
+    #[test]
+    fn grid_spans_range_of_span_ends() {
+        let xs = opt(&[]).grid(2.0, 12.0).unwrap();
+        assert_eq!(xs.len(), DEFAULT_GRID_POINTS);
+        assert_eq!((xs[0], xs[xs.len() - 1]), (2.0, 12.0));
+
+        let xs = opt(&["--grid-points", "3"]).grid(2.0, 12.0).unwrap();
+        assert_eq!(xs, vec![2.0, 7.0, 12.0]);
+    }
+
+    #[test]
+    fn step_grid_consists_of_multiples_within_range() {
+        let opt = opt(&["--grid-step", "0.5"]);
+        assert_eq!(opt.grid(0.7, 2.2).unwrap(), vec![1.0, 1.5, 2.0]);
+        assert_eq!(opt.grid(1.0, 2.0).unwrap(), vec![1.0, 1.5, 2.0]);
+        assert_eq!(opt.grid(-1.0, 0.2).unwrap(), vec![-1.0, -0.5, 0.0]);
+        assert_eq!(opt.grid(0.1, 0.4).unwrap(), Vec::<f64>::new());
+    }
+
+    #[test]
+    fn log_grid_keeps_both_ends() {
+        let xs = opt(&["--log-grid", "--grid-points", "3"])
+            .grid(1.0, 100.0)
+            .unwrap();
+        assert_eq!((xs[0], xs[2]), (1.0, 100.0));
+        assert!((xs[1] - 10.0).abs() < 1e-9);
+
+        let xs = opt(&["--log-grid"]).grid(0.3, 7.0).unwrap();
+        assert_eq!(xs.len(), DEFAULT_GRID_POINTS);
+        assert_eq!((xs[0], xs[xs.len() - 1]), (0.3, 7.0));
+        assert!(xs.windows(2).all(|w| w[0] < w[1]));
+
+        let xs = opt(&["--log-grid", "--grid-points", "1"])
+            .grid(0.3, 7.0)
+            .unwrap();
+        assert_eq!(xs, vec![0.3]);
+
+        assert!(opt(&["--log-grid"]).grid(0.0, 7.0).is_err());
+    }
 }
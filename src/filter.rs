@@ -0,0 +1,219 @@
+use crate::curve::LuaScript;
+use crate::io::InputOpt;
+use crate::utils::eval_state_name;
+use hporecord::{EvalRecord, ParamRange, Record, StudyRecord};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct FilterOpt {
+    #[structopt(flatten)]
+    pub input: InputOpt,
+
+    /// Lua predicate over `id`, `attrs`, `params`, `values` and `spans` of a study.
+    ///
+    /// The evaluations of the dropped studies are also dropped.
+    #[structopt(long)]
+    pub study: Option<LuaScript>,
+
+    /// Lua predicate over `params`, `values`, `state` and `spans` of an evaluation.
+    ///
+    /// The study of the evaluation is available as `study` (and its attributes as `attrs`).
+    #[structopt(long)]
+    pub eval: Option<LuaScript>,
+}
+
+impl FilterOpt {
+    pub fn filter<I, W>(&self, records: I, mut writer: W) -> anyhow::Result<()>
+    where
+        I: Iterator<Item = anyhow::Result<Record>>,
+        W: Write,
+    {
+        let lua = rlua::Lua::new();
+        let mut studies = BTreeMap::new();
+        let mut skipped_studies = BTreeSet::new();
+        let mut stats = Stats::default();
+        for record in records {
+            let record = record?;
+            let keep = match &record {
+                Record::Study(study) => {
+                    stats.studies += 1;
+                    let keep = if let Some(script) = &self.study {
+                        eval_study_predicate(&lua, script, study)?
+                    } else {
+                        true
+                    };
+                    if keep {
+                        stats.kept_studies += 1;
+                        studies.insert(study.id.clone(), Some(study.clone()));
+                    } else {
+                        studies.insert(study.id.clone(), None);
+                    }
+                    keep
+                }
+                Record::Eval(eval) => {
+                    stats.evals += 1;
+                    let keep = match studies.get(&eval.study) {
+                        None => {
+                            if !skipped_studies.contains(&eval.study) {
+                                eprintln!("[WARN] Unknown study: {:?}", eval.study);
+                                skipped_studies.insert(eval.study.clone());
+                            }
+                            false
+                        }
+                        Some(None) => false,
+                        Some(Some(study)) => {
+                            if let Some(script) = &self.eval {
+                                eval_eval_predicate(&lua, script, study, eval)?
+                            } else {
+                                true
+                            }
+                        }
+                    };
+                    if keep {
+                        stats.kept_evals += 1;
+                    }
+                    keep
+                }
+            };
+
+            if keep {
+                serde_json::to_writer(&mut writer, &record)?;
+                writeln!(writer)?;
+            }
+        }
+        writer.flush()?;
+
+        eprintln!(
+            "Kept {} of {} studies and {} of {} evaluations",
+            stats.kept_studies, stats.studies, stats.kept_evals, stats.evals
+        );
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    studies: usize,
+    kept_studies: usize,
+    evals: usize,
+    kept_evals: usize,
+}
+
+fn eval_study_predicate(
+    lua: &rlua::Lua,
+    script: &str,
+    study: &StudyRecord,
+) -> anyhow::Result<bool> {
+    let keep = lua.context(|lua_ctx| {
+        let env = new_environment(lua_ctx)?;
+        set_study_fields(lua_ctx, &env, study)?;
+        lua_ctx.load(script).set_environment(env)?.eval()
+    })?;
+    Ok(keep)
+}
+
+fn eval_eval_predicate(
+    lua: &rlua::Lua,
+    script: &str,
+    study: &StudyRecord,
+    eval: &EvalRecord,
+) -> anyhow::Result<bool> {
+    let keep = lua.context(|lua_ctx| {
+        let env = new_environment(lua_ctx)?;
+        let study_table = lua_ctx.create_table()?;
+        set_study_fields(lua_ctx, &study_table, study)?;
+        env.set("study", study_table)?;
+        env.set("attrs", study.attrs.clone())?;
+
+        let params = lua_ctx.create_table()?;
+        for (def, &p) in study.params.iter().zip(eval.params.iter()) {
+            match &def.range {
+                ParamRange::Categorical { choices } if p >= 0.0 && (p as usize) < choices.len() => {
+                    params.set(def.name.as_str(), choices[p as usize].as_str())?;
+                }
+                _ => {
+                    params.set(def.name.as_str(), p)?;
+                }
+            }
+        }
+        env.set("params", params)?;
+
+        let values = lua_ctx.create_table()?;
+        for (def, &v) in study.values.iter().zip(eval.values.iter()) {
+            values.set(def.name.as_str(), v)?;
+        }
+        env.set("values", values)?;
+
+        let spans = lua_ctx.create_table()?;
+        for (def, span) in study.spans.iter().zip(eval.spans.iter()) {
+            let s = lua_ctx.create_table()?;
+            s.set("start", span.start)?;
+            s.set("end", span.end)?;
+            spans.set(def.name.as_str(), s)?;
+        }
+        env.set("spans", spans)?;
+
+        env.set("state", eval_state_name(&eval.state))?;
+
+        lua_ctx.load(script).set_environment(env)?.eval()
+    })?;
+    Ok(keep)
+}
+
+/// Makes a fresh environment for a predicate so that the variables set for (or by) the previous
+/// records do not leak into it.
+///
+/// The global variables such as the standard libraries are still readable through the metatable.
+fn new_environment(lua_ctx: rlua::Context) -> rlua::Result<rlua::Table> {
+    let env = lua_ctx.create_table()?;
+    let meta = lua_ctx.create_table()?;
+    meta.set("__index", lua_ctx.globals())?;
+    env.set_metatable(Some(meta));
+    Ok(env)
+}
+
+fn set_study_fields<'lua>(
+    lua_ctx: rlua::Context<'lua>,
+    table: &rlua::Table<'lua>,
+    study: &StudyRecord,
+) -> rlua::Result<()> {
+    table.set("id", study.id.as_str())?;
+    table.set("attrs", study.attrs.clone())?;
+
+    let params = lua_ctx.create_table()?;
+    for def in &study.params {
+        let p = lua_ctx.create_table()?;
+        match &def.range {
+            ParamRange::Numerical {
+                min, max, scale, ..
+            } => {
+                p.set("min", *min)?;
+                p.set("max", *max)?;
+                p.set("scale", format!("{:?}", scale))?;
+            }
+            ParamRange::Categorical { choices } => {
+                p.set("choices", choices.clone())?;
+            }
+        }
+        params.set(def.name.as_str(), p)?;
+    }
+    table.set("params", params)?;
+
+    let values = lua_ctx.create_table()?;
+    for def in &study.values {
+        values.set(def.name.as_str(), format!("{:?}", def.direction))?;
+    }
+    table.set("values", values)?;
+
+    let spans = study
+        .spans
+        .iter()
+        .map(|s| s.name.clone())
+        .collect::<Vec<_>>();
+    table.set("spans", spans)?;
+
+    Ok(())
+}
@@ -1,188 +1,1333 @@
-use anyhow::ensure;
-use hporecord::{EvalState, ParamDef, Record, StudyId, StudyRecord};
-//use indicatif::ProgressBar;
-use crate::utils::MeanAndStddev;
+use crate::io::InputOpt;
+use crate::parallel::ParallelOpt;
+use crate::progress;
+use crate::scale::ScaleOpt;
+use crate::utils::{diff_defs, eval_study_script, quantile, MeanAndStddev};
+use anyhow::{anyhow, ensure, Context};
+use hporecord::{
+    EvalRecord, EvalState, ParamDef, ParamRange, Record, StudyId, StudyRecord, ValueDef,
+};
+use indicatif::ProgressBar;
 use itertools::Itertools;
 use ordered_float::OrderedFloat;
-use rand::seq::SliceRandom;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::num::NonZeroUsize;
 use structopt::StructOpt;
 
-pub type Importances = BTreeMap<StudyId, Vec<Importance>>;
+pub use self::estimator::{Estimator, EstimatorKind, TooFewSamples};
+
+mod estimator;
+mod forest;
+mod rank;
+
+pub type Importances = BTreeMap<StudyId, StudyImportances>;
+
+/// Importances keyed by objective name.
+pub type ObjectiveImportances = BTreeMap<String, Vec<Importance>>;
 
 #[derive(Debug, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 pub struct ImportanceOpt {
-    #[structopt(long, default_value = "0")]
-    pub objective_value_index: usize,
+    #[structopt(flatten)]
+    pub input: InputOpt,
+
+    /// Objective to be analyzed, specified by its name or index (default: the first objective).
+    ///
+    /// This option can be specified multiple times.
+    #[structopt(long = "objective", number_of_values = 1)]
+    pub objectives: Vec<String>,
+
+    /// Analyzes all the objectives of each study.
+    #[structopt(long, conflicts_with = "objectives")]
+    pub all_objectives: bool,
+
+    /// Deprecated: use `--objective` instead.
+    #[structopt(long, hidden = true, conflicts_with_all = &["objectives", "all-objectives"])]
+    pub objective_value_index: Option<usize>,
 
     #[structopt(long, default_value = "1")]
     pub max_dimension: NonZeroUsize,
 
+    /// Method used to estimate the importances.
+    #[structopt(long, default_value = "fanova", possible_values = EstimatorKind::NAMES)]
+    pub estimator: EstimatorKind,
+
     #[structopt(long, default_value = "1000")]
     pub max_samples: NonZeroUsize,
 
+    /// Only evaluates the interactions among the K most important parameters (by first-order importance).
     #[structopt(long)]
+    pub interaction_top_k: Option<usize>,
+
+    /// Drops the parameter combinations whose importances are less than this value.
+    #[structopt(long)]
+    pub min_importance: Option<f64>,
+
+    /// Emits at most N most important parameter combinations per objective.
+    #[structopt(long)]
+    pub top: Option<usize>,
+
+    #[structopt(flatten)]
+    pub scale: ScaleOpt,
+
+    /// Deprecated: the log scales in parameter definitions are now followed by default.
+    #[structopt(long, hidden = true)]
     pub convert_log_param: bool,
 
+    /// How to handle inactive (NaN) parameters of conditional search spaces.
+    ///
+    /// `sentinel` imputes an extra choice to categorical parameters and a value below the range to numerical ones.
+    /// `active-only` uses only the evaluations in which all the parameters are active.
+    #[structopt(long, default_value = "sentinel", possible_values = InactiveTreatment::NAMES)]
+    pub inactive_treatment: InactiveTreatment,
+
     #[structopt(long)]
     pub key_script: Option<String>,
+
+    /// Calculates importances of each study separately and aggregates them per key (see `--key-script`).
+    ///
+    /// By default, the evaluations of the studies mapped to the same key are pooled.
+    #[structopt(long, conflicts_with = "bootstrap")]
+    pub aggregate_per_study: bool,
+
+    /// Random seed used for subsampling and the importance estimators (chosen randomly if omitted).
+    #[structopt(long)]
+    pub seed: Option<u64>,
+
+    /// Index of the span used by `--span-min`, `--span-max` and `--bucket-width`.
+    #[structopt(long, default_value = "0")]
+    pub span_index: usize,
+
+    /// Only uses evaluations whose span ends at or after this value.
+    #[structopt(long)]
+    pub span_min: Option<f64>,
+
+    /// Only uses evaluations whose span ends before this value.
+    #[structopt(long)]
+    pub span_max: Option<f64>,
+
+    /// Also calculates importances per budget bucket `[k * width, (k + 1) * width)` of span ends.
+    #[structopt(long)]
+    pub bucket_width: Option<f64>,
+
+    /// Only uses this fraction of the best complete evaluations of each objective.
+    ///
+    /// The evaluations are subsampled to `--max-samples` / q beforehand so that up to `--max-samples`
+    /// of them are fitted.
+    #[structopt(long)]
+    pub top_quantile: Option<f64>,
+
+    /// Number of bootstrap replicates used to estimate the confidence intervals of the importances.
+    ///
+    /// The replicates are resampled from the (at most `--max-samples`) subsampled evaluations, so the
+    /// intervals do not reflect the variation due to which evaluations were subsampled.
+    #[structopt(long)]
+    pub bootstrap: Option<NonZeroUsize>,
+
+    /// Confidence level of the bootstrap percentile intervals.
+    #[structopt(long, default_value = "0.95")]
+    pub confidence: f64,
+
+    #[structopt(flatten)]
+    pub parallel: ParallelOpt,
 }
 
 impl ImportanceOpt {
-    pub fn calculate_importances(
-        &self,
-        records: &[Record],
-    ) -> anyhow::Result<BTreeMap<StudyId, Vec<Importance>>> {
-        let studies = self.build_studies(records)?;
+    pub fn calculate_importances<I>(&self, records: I) -> anyhow::Result<ImportanceReport>
+    where
+        I: Iterator<Item = anyhow::Result<Record>>,
+    {
+        if let Some(width) = self.bucket_width {
+            ensure!(width > 0.0, "the bucket width must be positive");
+        }
+        if self.objective_value_index.is_some() {
+            eprintln!("[WARN] `--objective-value-index` is deprecated (use `--objective` instead)");
+        }
+        if self.convert_log_param {
+            eprintln!(
+                "[WARN] `--convert-log-param` is deprecated and has no effect \
+                 (log-scale parameters are transformed by default; see `--no-auto-log-scale`)"
+            );
+        }
+        if let Some(max) = self.estimator.max_dimension() {
+            ensure!(
+                self.max_dimension.get() <= max,
+                "the {:?} estimator supports only `--max-dimension {}` or less",
+                self.estimator,
+                max
+            );
+        }
+        if let Some(q) = self.top_quantile {
+            ensure!(
+                q > 0.0 && q <= 1.0,
+                "the top quantile must be in the range (0, 1]"
+            );
+        }
+        ensure!(
+            self.confidence > 0.0 && self.confidence < 1.0,
+            "the confidence level must be in the range (0, 1)"
+        );
+
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let loading = progress::record_spinner();
+        let studies = self.build_studies(records.inspect(|_| loading.inc(1)), seed)?;
+        loading.finish_and_clear();
+
+        let pb = progress::study_bar(studies.len());
+        let studies = studies.into_iter().collect::<Vec<_>>();
+        let fit = FitSetting {
+            seed,
+            // The forests of fANOVA are fitted in parallel only if the studies are not, so that
+            // the number of threads is not multiplied.
+            parallel: self.parallel.jobs() > 1 && studies.len() < 2,
+            first_order_only: false,
+        };
+        let results = self.parallel.map(&studies, |(study_id, study)| {
+            pb.set_prefix(study_id);
+            let result = self.quantify_study(study_id, study, fit, &pb);
+            pb.inc(1);
+            result
+        });
+        pb.finish_and_clear();
 
         let mut result = BTreeMap::new();
-        for (study_id, study) in studies {
-            let mut importances = Vec::new();
-
-            let mut fanova = fanova::FanovaOptions::new().parallel().fit(
-                study.params.iter().map(|p| p.as_slice()).collect(),
-                &study.values,
-            )?;
-
-            for dim in 1..=self.max_dimension.get() {
-                for indices in (0..study.param_defs.len()).combinations(dim) {
-                    let importance = fanova.quantify_importance(&indices);
-                    importances.push(Importance {
-                        params: indices
-                            .into_iter()
-                            .map(|i| study.param_defs[i].name.clone())
-                            .collect(),
-                        importance: MeanAndStddev {
-                            mean: importance.mean,
-                            stddev: importance.stddev,
-                        },
-                    });
-                }
+        for ((study_id, _), importances) in studies.into_iter().zip(results) {
+            if let Some(importances) = importances? {
+                result.insert(study_id, importances);
+            }
+        }
+        Ok(ImportanceReport {
+            seed,
+            estimator: self.estimator,
+            inactive_treatment: self.inactive_treatment,
+            span_window: self.span_window(),
+            top_quantile: self.top_quantile,
+            pruning: self.pruning(),
+            bootstrap: self.bootstrap.map(|replicates| BootstrapSetting {
+                replicates: replicates.get(),
+                confidence: self.confidence,
+            }),
+            importances: result,
+        })
+    }
+
+    fn quantify_study(
+        &self,
+        study_id: &str,
+        study: &Study,
+        fit: FitSetting,
+        pb: &ProgressBar,
+    ) -> anyhow::Result<Option<StudyImportances>> {
+        let inactive_params = study
+            .param_defs
+            .iter()
+            .zip(study.inactive.iter())
+            .filter(|(_, &n)| n > 0)
+            .map(|(def, &n)| (def.name.clone(), n))
+            .collect();
+
+        if !self.estimator.supports_categorical() {
+            let categorical = study
+                .param_defs
+                .iter()
+                .filter(|def| is_categorical(def))
+                .map(|def| def.name.as_str())
+                .collect::<Vec<_>>();
+            if !categorical.is_empty() {
+                progress::println(pb, format!(
+                    "[WARN] Skipped the categorical parameters {:?} of the key {:?} (not supported by the {:?} estimator)",
+                    categorical, study_id, self.estimator
+                ));
+            }
+        }
+
+        if !self.aggregate_per_study {
+            let member = study.members.values().next().expect("unreachable");
+            if member.samples.len() < self.estimator.min_samples() {
+                progress::println(
+                    pb,
+                    format!("[WARN] Skipped the key {:?} (too few samples)", study_id),
+                );
+                return Ok(None);
+            }
+            let mut importances = self.quantify_member(study_id, study, member, None, fit, pb)?;
+            self.prune(&mut importances);
+            importances.inactive_params = inactive_params;
+            return Ok(Some(importances));
+        }
+
+        let mut members = Vec::new();
+        for (member_id, member) in &study.members {
+            if member.samples.len() < self.estimator.min_samples() {
+                progress::println(
+                    pb,
+                    format!(
+                        "[WARN] Skipped the study {:?} of the key {:?} (too few samples)",
+                        member_id, study_id
+                    ),
+                );
+                continue;
             }
+            members.push((member_id, member));
+        }
+        if members.is_empty() {
+            progress::println(
+                pb,
+                format!("[WARN] Skipped the key {:?} (no studies)", study_id),
+            );
+            return Ok(None);
+        }
 
-            importances.sort_by_key(|i| OrderedFloat(i.importance.mean));
-            importances.reverse();
-            result.insert(study_id, importances);
+        // The interactions are evaluated among the same parameters in all the studies, which are
+        // chosen by the first-order importances aggregated beforehand.
+        let first_order = if self.interaction_top_k.is_some() && self.max_dimension.get() > 1 {
+            let first_order_fit = FitSetting {
+                first_order_only: true,
+                ..fit
+            };
+            let results = members
+                .iter()
+                .map(|(id, m)| self.quantify_member(id, study, m, None, first_order_fit, pb))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            Some(aggregate_importances(&results))
+        } else {
+            None
+        };
+        let results = members
+            .iter()
+            .map(|(id, m)| self.quantify_member(id, study, m, first_order.as_ref(), fit, pb))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut importances = aggregate_importances(&results);
+        self.prune(&mut importances);
+        importances.inactive_params = inactive_params;
+        Ok(Some(importances))
+    }
+
+    /// Applies `--min-importance` and `--top` to the (aggregated) importances.
+    fn prune(&self, importances: &mut StudyImportances) {
+        self.prune_objectives(&mut importances.objectives, &mut importances.pruned);
+        for bucket in &mut importances.buckets {
+            self.prune_objectives(&mut bucket.objectives, &mut bucket.pruned);
         }
-        Ok(result)
     }
 
-    fn build_studies(&self, records: &[Record]) -> anyhow::Result<BTreeMap<StudyId, Study>> {
-        let mut id_mapping = BTreeMap::new();
-        if let Some(script) = &self.key_script {
-            for record in records {
-                if let Record::Study(study) = record {
-                    let lua = rlua::Lua::new();
-                    let new_id: String = lua.context(|lua_ctx| {
-                        let globals = lua_ctx.globals();
-
-                        // TODO
-                        globals.set("attrs", study.attrs.clone())?;
-
-                        lua_ctx.load(&script).eval()
-                    })?;
-                    id_mapping.insert(&study.id, new_id);
+    fn prune_objectives(
+        &self,
+        objectives: &mut ObjectiveImportances,
+        pruned_objectives: &mut BTreeMap<String, Pruned>,
+    ) {
+        if self.min_importance.is_none() && self.top.is_none() {
+            return;
+        }
+        for (objective, importances) in objectives {
+            let pruned = pruned_objectives.entry(objective.clone()).or_default();
+            if let Some(min) = self.min_importance {
+                let n = importances.len();
+                importances.retain(|im| im.importance.mean >= min);
+                pruned.below_min_importance = n - importances.len();
+            }
+            if let Some(top) = self.top {
+                pruned.beyond_top = importances.len().saturating_sub(top);
+                importances.truncate(top);
+            }
+        }
+    }
+
+    /// Quantifies the importances of a study (and its buckets).
+    ///
+    /// If `first_order` is given, the parameters for `--interaction-top-k` are chosen by its
+    /// first-order importances instead of those of the study itself.
+    fn quantify_member(
+        &self,
+        member_id: &str,
+        study: &Study,
+        member: &Member,
+        first_order: Option<&StudyImportances>,
+        fit: FitSetting,
+        pb: &ProgressBar,
+    ) -> anyhow::Result<StudyImportances> {
+        let target = format!("the study {:?}", member_id);
+        let (objectives, pruned) = self.quantify_objectives(
+            &target,
+            study,
+            &member.samples,
+            first_order.map(|f| &f.objectives),
+            fit,
+            pb,
+        )?;
+
+        let mut buckets = Vec::new();
+        for (&k, samples) in &member.buckets {
+            let width = self.bucket_width.expect("unreachable");
+            let (start, end) = (k as f64 * width, (k + 1) as f64 * width);
+            if samples.len() < self.estimator.min_samples() {
+                progress::println(
+                    pb,
+                    format!(
+                        "[WARN] Skipped the bucket [{}, {}) of the study {:?} (too few samples)",
+                        start, end, member_id
+                    ),
+                );
+                continue;
+            }
+            let target = format!(
+                "the bucket [{}, {}) of the study {:?}",
+                start, end, member_id
+            );
+            let bucket_first_order = first_order.map(|f| {
+                f.buckets
+                    .iter()
+                    .find(|b| b.start == start)
+                    .map(|b| &b.objectives)
+                    .expect("unreachable")
+            });
+            let (objectives, pruned) =
+                self.quantify_objectives(&target, study, samples, bucket_first_order, fit, pb)?;
+            buckets.push(BucketImportances {
+                start,
+                end,
+                samples: samples.len(),
+                objectives,
+                pruned,
+            });
+        }
+
+        Ok(StudyImportances {
+            objectives,
+            buckets,
+            studies: None,
+            pruned,
+            inactive_params: BTreeMap::new(),
+        })
+    }
+
+    /// Quantifies the importances of each objective of `target` (used in warnings).
+    ///
+    /// Objectives with too few samples for the estimator are skipped.
+    fn quantify_objectives(
+        &self,
+        target: &str,
+        study: &Study,
+        samples: &Samples,
+        first_order: Option<&ObjectiveImportances>,
+        fit: FitSetting,
+        pb: &ProgressBar,
+    ) -> anyhow::Result<(ObjectiveImportances, BTreeMap<String, Pruned>)> {
+        let mut objectives = BTreeMap::new();
+        let mut pruned_objectives = BTreeMap::new();
+        for (objective, values) in study.objectives.iter().zip(samples.values.iter()) {
+            let indices = if let Some(q) = self.top_quantile {
+                top_quantile_indices(objective, values, q, self.estimator.min_samples())
+            } else {
+                (0..values.len()).collect()
+            };
+            let (params, values) = select_rows(&samples.params, values, &indices);
+            let quantified = if values.len() < self.estimator.min_samples() {
+                Err(TooFewSamples.into())
+            } else {
+                self.quantify_importances(
+                    &study.param_defs,
+                    &params,
+                    &values,
+                    first_order.map(|f| f.get(&objective.name).map_or(&[][..], |f| f)),
+                    fit,
+                    pb,
+                )
+            };
+            let (mut importances, pruned) = match quantified {
+                Err(e) if e.is::<TooFewSamples>() => {
+                    progress::println(
+                        pb,
+                        format!(
+                            "[WARN] Skipped the objective {:?} of {} (too few samples)",
+                            objective.name, target
+                        ),
+                    );
+                    continue;
+                }
+                result => result?,
+            };
+            if let Some(replicates) = self.bootstrap {
+                let mut stats = self.bootstrap_importances(
+                    &study.param_defs,
+                    &params,
+                    &values,
+                    replicates.get(),
+                    fit,
+                    pb,
+                )?;
+                for im in &mut importances {
+                    im.bootstrap = stats.remove(&im.params);
                 }
             }
+            if self.pruning().is_some() {
+                pruned_objectives.insert(objective.name.clone(), pruned);
+            }
+            objectives.insert(objective.name.clone(), importances);
+        }
+        Ok((objectives, pruned_objectives))
+    }
+
+    /// Re-calculates the importances on `replicates` resamples (with replacement) of the samples
+    /// and returns the resulting statistics keyed by the parameter names.
+    fn bootstrap_importances(
+        &self,
+        param_defs: &[ParamDef],
+        params: &[Vec<f64>],
+        values: &[f64],
+        replicates: usize,
+        fit: FitSetting,
+        pb: &ProgressBar,
+    ) -> anyhow::Result<BTreeMap<Vec<String>, BootstrapStats>> {
+        let mut rng = StdRng::seed_from_u64(fit.seed);
+        let mut replicated = BTreeMap::<_, Vec<f64>>::new();
+        for r in 0..replicates {
+            let indices = (0..values.len())
+                .map(|_| rng.gen_range(0, values.len()))
+                .collect::<Vec<_>>();
+            let (params, values) = select_rows(params, values, &indices);
+            let replicate_fit = FitSetting {
+                seed: fit.seed.wrapping_add(r as u64 + 1),
+                ..fit
+            };
+            let (importances, _) = match self.quantify_importances(
+                param_defs,
+                &params,
+                &values,
+                None,
+                replicate_fit,
+                pb,
+            ) {
+                // E.g., no out-of-bag samples due to many duplicates in the resample.
+                Err(e) if e.is::<TooFewSamples>() => continue,
+                result => result?,
+            };
+            for im in importances {
+                replicated
+                    .entry(im.params)
+                    .or_default()
+                    .push(im.importance.mean);
+            }
+        }
+
+        Ok(replicated
+            .into_iter()
+            .map(|(params, mut means)| (params, BootstrapStats::new(&mut means, self.confidence)))
+            .collect())
+    }
+
+    /// Quantifies the importances of the parameters and their interactions.
+    ///
+    /// If `first_order` is given, the parameters for `--interaction-top-k` are chosen by the
+    /// first-order importances in it (which are sorted in descending order).
+    fn quantify_importances(
+        &self,
+        param_defs: &[ParamDef],
+        params: &[Vec<f64>],
+        values: &[f64],
+        first_order: Option<&[Importance]>,
+        fit: FitSetting,
+        pb: &ProgressBar,
+    ) -> anyhow::Result<(Vec<Importance>, Pruned)> {
+        pb.set_message(&format!("fitting {} samples", values.len()));
+        let supports_categorical = self.estimator.supports_categorical();
+        let params = if supports_categorical {
+            encode_params(param_defs, params, values)
+        } else {
+            params.to_vec()
+        };
+        let mut estimator = self
+            .estimator
+            .fit(&params, values, fit.seed, fit.parallel)?;
+
+        let n = param_defs.len();
+        let mut pruned = Pruned::default();
+        let singles = (0..n)
+            .filter(|&i| supports_categorical || !is_categorical(&param_defs[i]))
+            .map(|i| vec![i])
+            .collect();
+        let mut quantified = quantify_combinations(estimator.as_mut(), singles, pb)?;
+        if !self.estimator.is_variance_decomposition() {
+            normalize_importances(&mut quantified);
+        }
+
+        let mut candidates = (0..n).collect::<Vec<_>>();
+        if fit.first_order_only {
+            candidates.clear();
+        } else if let Some(k) = self.interaction_top_k {
+            if let Some(first_order) = first_order {
+                candidates = first_order
+                    .iter()
+                    .filter(|im| im.params.len() == 1)
+                    .filter_map(|im| param_defs.iter().position(|d| d.name == im.params[0]))
+                    .collect();
+            } else {
+                let mut singles = quantified
+                    .iter()
+                    .map(|(indices, im)| (indices[0], im.mean))
+                    .collect::<Vec<_>>();
+                singles.sort_by_key(|&(_, mean)| OrderedFloat(-mean));
+                candidates = singles.into_iter().map(|(i, _)| i).collect();
+            }
+            candidates.truncate(k);
+            candidates.sort_unstable();
+            pruned.interaction_params = Some(
+                candidates
+                    .iter()
+                    .map(|&i| param_defs[i].name.clone())
+                    .collect(),
+            );
         }
+        let interactions = (2..=self.max_dimension.get())
+            .flat_map(|dim| candidates.iter().copied().combinations(dim))
+            .collect::<Vec<_>>();
+        pruned.skipped_interactions = (2..=self.max_dimension.get())
+            .map(|dim| binomial(n, dim))
+            .sum::<usize>()
+            - interactions.len();
+        quantified.extend(quantify_combinations(estimator.as_mut(), interactions, pb)?);
+
+        let mut importances = Vec::new();
+        for (indices, importance) in quantified {
+            importances.push(Importance {
+                params: indices
+                    .iter()
+                    .map(|&i| param_defs[i].name.clone())
+                    .collect(),
+                categorical: indices
+                    .iter()
+                    .map(|&i| is_categorical(&param_defs[i]))
+                    .collect(),
+                importance,
+                bootstrap: None,
+            });
+        }
+
+        sort_importances(&mut importances);
+        Ok((importances, pruned))
+    }
 
-        // TODO: Handle categorical and log scale
-        let mut studies = BTreeMap::new();
+    fn pruning(&self) -> Option<PruningSetting> {
+        if self.interaction_top_k.is_none() && self.min_importance.is_none() && self.top.is_none() {
+            return None;
+        }
+        Some(PruningSetting {
+            interaction_top_k: self.interaction_top_k,
+            min_importance: self.min_importance,
+            top: self.top,
+        })
+    }
+
+    fn build_studies<I>(&self, records: I, seed: u64) -> anyhow::Result<BTreeMap<StudyId, Study>>
+    where
+        I: Iterator<Item = anyhow::Result<Record>>,
+    {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let lua = rlua::Lua::new();
+        let mut id_mapping = BTreeMap::new();
+
+        let mut studies: BTreeMap<StudyId, Study> = BTreeMap::new();
         for record in records {
-            match record {
+            match record? {
                 Record::Study(study) => {
-                    let study_id = id_mapping.get(&study.id).unwrap_or(&study.id);
-                    if !studies.contains_key(study_id) {
-                        studies.insert(study_id.clone(), Study::new(study));
+                    let study_id = if let Some(script) = &self.key_script {
+                        eval_study_script(&lua, script, &study)?
+                    } else {
+                        study.id.clone()
+                    };
+                    if let Some(existing) = studies.get(&study_id) {
+                        let diffs = diff_defs("params", &existing.param_defs, &study.params)?;
+                        ensure!(
+                            diffs.is_empty(),
+                            "the study {:?} has parameter definitions different from the others of the key {:?}:\n  {}",
+                            study.id,
+                            study_id,
+                            diffs.join("\n  ")
+                        );
                     } else {
-                        // TODO: Check whether the parameter definitions of the both studies are the same.
+                        let objective_indices = self
+                            .select_objectives(&study)
+                            .with_context(|| format!("study={:?}", study.id))?;
+                        studies.insert(study_id.clone(), Study::new(&study, objective_indices));
                     }
+                    let member_id = self.member_id(&study.id, &study_id);
+                    studies
+                        .get_mut(&study_id)
+                        .expect("unreachable")
+                        .add_member(member_id);
+                    id_mapping.insert(study.id, study_id);
                 }
                 Record::Eval(eval) => {
-                    let study_id = id_mapping.get(&eval.study).unwrap_or(&eval.study);
-
                     ensure!(
-                        studies.contains_key(study_id),
+                        id_mapping.contains_key(&eval.study),
                         "unknown study {:?}",
                         eval.study
                     );
                     if eval.state != EvalState::Complete {
                         continue;
                     }
+                    let span_end = self
+                        .span_end(&eval)
+                        .with_context(|| format!("study={:?}", eval.study))?;
+                    if let Some(end) = span_end {
+                        if matches!(self.span_min, Some(min) if end < min)
+                            || matches!(self.span_max, Some(max) if end >= max)
+                        {
+                            continue;
+                        }
+                    }
 
+                    let study_id = id_mapping.get(&eval.study).expect("unreachable");
+                    let member_id = self.member_id(&eval.study, study_id);
                     let study = studies.get_mut(study_id).expect("unreachable");
-                    for ((&p, ps), def) in eval
+                    let mut params = eval
                         .params
                         .iter()
-                        .zip(study.params.iter_mut())
                         .zip(study.param_defs.iter())
-                    {
-                        use hporecord::{ParamRange, Scale};
-
-                        if self.convert_log_param
-                            && matches!(def.range, ParamRange::Numerical{scale: Scale::Log,..})
-                        {
-                            ps.push(p.ln());
-                        } else {
-                            ps.push(p);
+                        .map(|(&p, def)| self.scale.transform(def, p))
+                        .collect::<anyhow::Result<Vec<_>>>()
+                        .with_context(|| format!("study={:?}", eval.study))?;
+                    if params.iter().any(|p| p.is_nan()) {
+                        for (n, p) in study.inactive.iter_mut().zip(params.iter()) {
+                            if p.is_nan() {
+                                *n += 1;
+                            }
+                        }
+                        if self.inactive_treatment == InactiveTreatment::ActiveOnly {
+                            continue;
+                        }
+                        for (p, def) in params.iter_mut().zip(study.param_defs.iter()) {
+                            if p.is_nan() {
+                                *p = self.sentinel(def)?;
+                            }
                         }
                     }
-                    ensure!(
-                        self.objective_value_index < eval.values.len(),
-                        "the objective value index {} is out of range (must be less than {})",
-                        self.objective_value_index,
-                        eval.values.len()
-                    );
-                    study.values.push(eval.values[self.objective_value_index]);
+                    let values = study
+                        .objective_indices
+                        .iter()
+                        .map(|&i| {
+                            eval.values.get(i).copied().ok_or_else(|| {
+                                anyhow!(
+                                    "the objective value index {} is out of range (must be less than {})",
+                                    i,
+                                    eval.values.len()
+                                )
+                            })
+                        })
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    let (n_params, n_objectives) = (study.param_defs.len(), study.objectives.len());
+                    let member = study.members.get_mut(member_id).expect("unreachable");
+                    member
+                        .samples
+                        .push(&mut rng, self.reservoir_size(), &params, &values);
+                    if let (Some(width), Some(end)) = (self.bucket_width, span_end) {
+                        let k = (end / width).floor() as i64;
+                        member
+                            .buckets
+                            .entry(k)
+                            .or_insert_with(|| Samples::new(n_params, n_objectives))
+                            .push(&mut rng, self.reservoir_size(), &params, &values);
+                    }
                 }
             }
         }
 
-        for study in studies.values_mut() {
-            if self.max_samples.get() < study.values.len() {
-                let mut indices = (0..study.values.len()).collect::<Vec<_>>();
-                let (shuffled_indices, _) =
-                    indices.partial_shuffle(&mut rand::thread_rng(), self.max_samples.get());
-                let mut params =
-                    vec![Vec::with_capacity(self.max_samples.get()); study.params.len()];
-                let mut values = Vec::with_capacity(self.max_samples.get());
-                for &mut i in shuffled_indices {
-                    for j in 0..study.params.len() {
-                        params[j].push(study.params[j][i]);
-                    }
-                    values.push(study.values[i]);
-                }
-                study.params = params;
-                study.values = values;
+        Ok(studies)
+    }
+
+    /// Returns the number of the evaluations subsampled per member (and bucket).
+    fn reservoir_size(&self) -> usize {
+        let max_samples = self.max_samples.get();
+        if let Some(q) = self.top_quantile {
+            ((max_samples as f64 / q).floor() as usize).max(max_samples)
+        } else {
+            max_samples
+        }
+    }
+
+    /// Returns the value imputed to an inactive parameter (see `InactiveTreatment::Sentinel`).
+    fn sentinel(&self, def: &ParamDef) -> anyhow::Result<f64> {
+        match &def.range {
+            ParamRange::Categorical { choices } => Ok(choices.len() as f64),
+            ParamRange::Numerical { min, max, .. } => {
+                let min = self.scale.transform(def, *min)?;
+                let max = self.scale.transform(def, *max)?;
+                let width = max - min;
+                Ok(min - if width > 0.0 { width } else { 1.0 })
             }
         }
+    }
 
-        Ok(studies)
+    /// Returns the id of the member (i.e., sample set) of the key `key` to which the study `study_id` belongs.
+    fn member_id<'a>(&self, study_id: &'a str, key: &'a str) -> &'a str {
+        if self.aggregate_per_study {
+            study_id
+        } else {
+            key
+        }
+    }
+
+    fn span_window(&self) -> Option<SpanWindow> {
+        if self.span_min.is_none() && self.span_max.is_none() && self.bucket_width.is_none() {
+            return None;
+        }
+        Some(SpanWindow {
+            span_index: self.span_index,
+            min: self.span_min,
+            max: self.span_max,
+            bucket_width: self.bucket_width,
+        })
+    }
+
+    fn span_end(&self, eval: &EvalRecord) -> anyhow::Result<Option<f64>> {
+        if self.span_window().is_none() {
+            return Ok(None);
+        }
+        let span = eval.spans.get(self.span_index).ok_or_else(|| {
+            anyhow!(
+                "the span index {} is out of range (must be less than {})",
+                self.span_index,
+                eval.spans.len()
+            )
+        })?;
+        Ok(Some(span.end))
+    }
+
+    fn select_objectives(&self, study: &StudyRecord) -> anyhow::Result<Vec<usize>> {
+        ensure!(!study.values.is_empty(), "the study has no objectives");
+        if self.all_objectives {
+            return Ok((0..study.values.len()).collect());
+        }
+        if let Some(i) = self.objective_value_index {
+            return Ok(vec![objective_index(study, i)?]);
+        }
+        if self.objectives.is_empty() {
+            return Ok(vec![0]);
+        }
+
+        self.objectives
+            .iter()
+            .map(|objective| {
+                if let Some(i) = study.values.iter().position(|v| v.name == *objective) {
+                    return Ok(i);
+                }
+                let i = objective
+                    .parse()
+                    .map_err(|_| anyhow!("unknown objective {:?}", objective))?;
+                objective_index(study, i)
+            })
+            .collect()
     }
 }
 
 #[derive(Debug)]
 pub struct Study {
     param_defs: Vec<ParamDef>,
-    params: Vec<Vec<f64>>,
-    values: Vec<f64>,
+    objectives: Vec<ValueDef>,
+    objective_indices: Vec<usize>,
+    members: BTreeMap<StudyId, Member>,
+
+    /// Number of the complete evaluations in which each parameter is inactive.
+    inactive: Vec<usize>,
 }
 
 impl Study {
-    fn new(record: &StudyRecord) -> Self {
+    fn new(record: &StudyRecord, objective_indices: Vec<usize>) -> Self {
         Self {
             param_defs: record.params.clone(),
-            params: vec![Vec::new(); record.params.len()],
-            values: Vec::new(),
+            objectives: objective_indices
+                .iter()
+                .map(|&i| record.values[i].clone())
+                .collect(),
+            members: BTreeMap::new(),
+            inactive: vec![0; record.params.len()],
+            objective_indices,
+        }
+    }
+
+    fn add_member(&mut self, member_id: &str) {
+        let (n_params, n_objectives) = (self.param_defs.len(), self.objectives.len());
+        self.members
+            .entry(member_id.to_owned())
+            .or_insert_with(|| Member {
+                samples: Samples::new(n_params, n_objectives),
+                buckets: BTreeMap::new(),
+            });
+    }
+}
+
+fn objective_index(study: &StudyRecord, i: usize) -> anyhow::Result<usize> {
+    ensure!(
+        i < study.values.len(),
+        "the objective index {} is out of range (must be less than {})",
+        i,
+        study.values.len()
+    );
+    Ok(i)
+}
+
+/// Samples to be fitted together.
+///
+/// A member is either a whole key or, with `--aggregate-per-study`, a single study of the key.
+#[derive(Debug)]
+struct Member {
+    samples: Samples,
+    buckets: BTreeMap<i64, Samples>,
+}
+
+/// Complete evaluations of a study, stored column by column.
+#[derive(Debug)]
+struct Samples {
+    params: Vec<Vec<f64>>,
+    values: Vec<Vec<f64>>,
+    evals: usize,
+}
+
+impl Samples {
+    fn new(params: usize, objectives: usize) -> Self {
+        Self {
+            params: vec![Vec::new(); params],
+            values: vec![Vec::new(); objectives],
+            evals: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.values.first().map_or(0, |vs| vs.len())
+    }
+
+    /// Adds a complete evaluation, keeping at most `max_samples` of them (reservoir sampling).
+    fn push<R: Rng>(&mut self, rng: &mut R, max_samples: usize, params: &[f64], values: &[f64]) {
+        self.evals += 1;
+
+        let i = if self.len() < max_samples {
+            for column in self.params.iter_mut().chain(self.values.iter_mut()) {
+                column.push(0.0);
+            }
+            self.len() - 1
+        } else {
+            let i = rng.gen_range(0, self.evals);
+            if i >= max_samples {
+                return;
+            }
+            i
+        };
+
+        for (ps, &p) in self.params.iter_mut().zip(params.iter()) {
+            ps[i] = p;
+        }
+        for (vs, &v) in self.values.iter_mut().zip(values.iter()) {
+            vs[i] = v;
+        }
+    }
+}
+
+/// Settings passed down to the importance estimators.
+#[derive(Debug, Clone, Copy)]
+struct FitSetting {
+    seed: u64,
+
+    /// Whether the estimator may use multiple threads (see `EstimatorKind::fit`).
+    parallel: bool,
+
+    /// Whether only the first-order importances are quantified.
+    first_order_only: bool,
+}
+
+/// Returns the indices of the best `q` fraction of `values` (but at least `min_samples` of them).
+///
+/// NaN values are regarded as the worst ones.
+fn top_quantile_indices(
+    objective: &ValueDef,
+    values: &[f64],
+    q: f64,
+    min_samples: usize,
+) -> Vec<usize> {
+    let n = ((values.len() as f64 * q).ceil() as usize)
+        .max(min_samples)
+        .min(values.len());
+    let mut indices = (0..values.len()).collect::<Vec<_>>();
+    indices.sort_by_key(|&i| {
+        let v = values[i];
+        OrderedFloat(if objective.direction.is_minimize() {
+            v
+        } else {
+            -v
+        })
+    });
+    indices.truncate(n);
+    indices.sort_unstable();
+    indices
+}
+
+fn quantify_combinations(
+    estimator: &mut dyn Estimator,
+    combinations: Vec<Vec<usize>>,
+    pb: &ProgressBar,
+) -> anyhow::Result<Vec<(Vec<usize>, MeanAndStddev)>> {
+    let total = combinations.len();
+    let mut quantified = Vec::with_capacity(total);
+    for (i, indices) in combinations.into_iter().enumerate() {
+        pb.set_message(&format!("combination {}/{}", i + 1, total));
+        let importance = estimator.quantify_importance(&indices)?;
+        quantified.push((indices, importance));
+    }
+    Ok(quantified)
+}
+
+/// Scales the importances so that they sum up to 1 like the fractions of variance of fANOVA.
+///
+/// Negative importances (e.g., permutation importances of irrelevant parameters) are regarded as 0.
+fn normalize_importances(quantified: &mut [(Vec<usize>, MeanAndStddev)]) {
+    for (_, im) in quantified.iter_mut() {
+        im.mean = im.mean.max(0.0);
+    }
+    let total = quantified.iter().map(|(_, im)| im.mean).sum::<f64>();
+    if total > 0.0 {
+        for (_, im) in quantified.iter_mut() {
+            im.mean /= total;
+            im.stddev /= total;
+        }
+    }
+}
+
+fn binomial(n: usize, k: usize) -> usize {
+    if k > n {
+        return 0;
+    }
+    (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+}
+
+fn sort_importances(importances: &mut [Importance]) {
+    importances.sort_by_key(|i| OrderedFloat(i.importance.mean));
+    importances.reverse();
+}
+
+/// Aggregates the importances of the studies of a key into their means and standard deviations.
+fn aggregate_importances(members: &[StudyImportances]) -> StudyImportances {
+    let objectives = aggregate_objectives(members.iter().map(|m| &m.objectives));
+
+    let mut buckets = BTreeMap::<_, Vec<_>>::new();
+    for bucket in members.iter().flat_map(|m| m.buckets.iter()) {
+        buckets
+            .entry(OrderedFloat(bucket.start))
+            .or_default()
+            .push(bucket);
+    }
+    let buckets = buckets
+        .into_values()
+        .map(|group| BucketImportances {
+            start: group[0].start,
+            end: group[0].end,
+            samples: group.iter().map(|b| b.samples).sum(),
+            objectives: aggregate_objectives(group.iter().map(|b| &b.objectives)),
+            pruned: aggregate_pruned(group.iter().map(|b| &b.pruned)),
+        })
+        .collect();
+
+    StudyImportances {
+        objectives,
+        buckets,
+        studies: Some(members.len()),
+        pruned: aggregate_pruned(members.iter().map(|m| &m.pruned)),
+        inactive_params: BTreeMap::new(),
+    }
+}
+
+/// Returns the skipped interactions of the studies of a key, which are the same in all of them.
+///
+/// `--min-importance` and `--top` are applied after the aggregation.
+fn aggregate_pruned<'a>(
+    results: impl Iterator<Item = &'a BTreeMap<String, Pruned>>,
+) -> BTreeMap<String, Pruned> {
+    let mut aggregated = BTreeMap::new();
+    for pruned_objectives in results {
+        for (objective, pruned) in pruned_objectives {
+            aggregated
+                .entry(objective.clone())
+                .or_insert_with(|| pruned.clone());
         }
     }
+    aggregated
+}
+
+fn aggregate_objectives<'a>(
+    results: impl Iterator<Item = &'a ObjectiveImportances>,
+) -> ObjectiveImportances {
+    let mut groups = BTreeMap::<_, BTreeMap<_, (_, Vec<_>)>>::new();
+    for objectives in results {
+        for (objective, importances) in objectives {
+            for im in importances {
+                groups
+                    .entry(objective.clone())
+                    .or_default()
+                    .entry(im.params.clone())
+                    .or_insert_with(|| (im.categorical.clone(), Vec::new()))
+                    .1
+                    .push(im.importance.mean);
+            }
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(objective, group)| {
+            let mut importances = group
+                .into_iter()
+                .map(|(params, (categorical, means))| Importance {
+                    params,
+                    categorical,
+                    importance: MeanAndStddev::from_samples(&means),
+                    bootstrap: None,
+                })
+                .collect::<Vec<_>>();
+            sort_importances(&mut importances);
+            (objective, importances)
+        })
+        .collect()
+}
+
+/// Returns the rows at `indices` of the parameter columns and the objective values.
+fn select_rows(
+    params: &[Vec<f64>],
+    values: &[f64],
+    indices: &[usize],
+) -> (Vec<Vec<f64>>, Vec<f64>) {
+    let params = params
+        .iter()
+        .map(|ps| indices.iter().map(|&i| ps[i]).collect())
+        .collect();
+    let values = indices.iter().map(|&i| values[i]).collect();
+    (params, values)
+}
+
+/// Returns the parameter columns to be fed to the importance estimator.
+///
+/// The choices of a categorical parameter are re-ordered by the mean objective value of their samples.
+/// Regression trees can then find the best binary partitions of the choices
+/// (Breiman et al., 1984) instead of splitting on their arbitrary index order.
+fn encode_params(param_defs: &[ParamDef], params: &[Vec<f64>], values: &[f64]) -> Vec<Vec<f64>> {
+    param_defs
+        .iter()
+        .zip(params.iter())
+        .map(|(def, ps)| {
+            if let ParamRange::Categorical { choices } = &def.range {
+                // The last choice stands for inactive (see `InactiveTreatment::Sentinel`).
+                let ranks = rank_choices(choices.len() + 1, ps, values);
+                ps.iter()
+                    .map(|&p| ranks.get(p as usize).map_or(p, |&r| r as f64))
+                    .collect()
+            } else {
+                ps.clone()
+            }
+        })
+        .collect()
+}
+
+fn rank_choices(choices: usize, ps: &[f64], values: &[f64]) -> Vec<usize> {
+    let mut sums = vec![(0.0, 0); choices];
+    for (&p, &v) in ps.iter().zip(values.iter()) {
+        if let Some(s) = sums.get_mut(p as usize) {
+            s.0 += v;
+            s.1 += 1;
+        }
+    }
+
+    // Unobserved choices are placed after the observed ones.
+    let mut order = (0..choices).collect::<Vec<_>>();
+    order.sort_by_key(|&i| {
+        let (sum, n) = sums[i];
+        (
+            n == 0,
+            OrderedFloat(if n == 0 { 0.0 } else { sum / n as f64 }),
+        )
+    });
+
+    let mut ranks = vec![0; choices];
+    for (rank, i) in order.into_iter().enumerate() {
+        ranks[i] = rank;
+    }
+    ranks
+}
+
+fn is_categorical(def: &ParamDef) -> bool {
+    matches!(def.range, ParamRange::Categorical { .. })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportanceReport {
+    /// Random seed used to calculate the importances.
+    pub seed: u64,
+
+    /// Method used to estimate the importances.
+    #[serde(default)]
+    pub estimator: EstimatorKind,
+
+    /// How inactive parameters were handled.
+    #[serde(default)]
+    pub inactive_treatment: InactiveTreatment,
+
+    /// Span-based restriction of the evaluations, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span_window: Option<SpanWindow>,
+
+    /// Fraction of the best evaluations used to calculate the importances (all if omitted).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_quantile: Option<f64>,
+
+    /// Bootstrap setting used to estimate the confidence intervals, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootstrap: Option<BootstrapSetting>,
+
+    /// Pruning setting of the parameter combinations, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pruning: Option<PruningSetting>,
+
+    pub importances: Importances,
+}
+
+/// Handling of inactive (NaN) parameters of conditional search spaces.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InactiveTreatment {
+    /// Imputes an extra choice to categorical parameters and a value below the range to numerical ones.
+    #[default]
+    Sentinel,
+
+    /// Uses only the evaluations in which all the parameters are active.
+    ActiveOnly,
+}
+
+impl InactiveTreatment {
+    pub const NAMES: &'static [&'static str] = &["sentinel", "active-only"];
+}
+
+impl std::str::FromStr for InactiveTreatment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sentinel" => Ok(Self::Sentinel),
+            "active-only" => Ok(Self::ActiveOnly),
+            _ => Err(anyhow!("unknown inactive treatment {:?}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpanWindow {
+    pub span_index: usize,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub bucket_width: Option<f64>,
+}
+
+/// Setting of the bootstrap within the subsampled evaluations (see `--bootstrap`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootstrapSetting {
+    pub replicates: usize,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PruningSetting {
+    pub interaction_top_k: Option<usize>,
+    pub min_importance: Option<f64>,
+    pub top: Option<usize>,
+}
+
+/// What was pruned from the importances of an objective.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Pruned {
+    /// Parameters among which the interactions were evaluated (see `--interaction-top-k`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interaction_params: Option<Vec<String>>,
+
+    /// Number of the interactions which were not evaluated.
+    pub skipped_interactions: usize,
+
+    /// Number of the combinations dropped because of `--min-importance`.
+    pub below_min_importance: usize,
+
+    /// Number of the combinations dropped because of `--top`.
+    pub beyond_top: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StudyImportances {
+    /// Importances keyed by objective name.
+    pub objectives: ObjectiveImportances,
+
+    /// Importances per budget bucket, in ascending order (see `--bucket-width`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buckets: Vec<BucketImportances>,
+
+    /// Number of the studies whose importances are aggregated (see `--aggregate-per-study`).
+    ///
+    /// If set, the importances are the means and standard deviations over the studies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub studies: Option<usize>,
+
+    /// What was pruned from the importances, keyed by objective name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pruned: BTreeMap<String, Pruned>,
+
+    /// Number of the complete evaluations in which each parameter is inactive (NaN).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub inactive_params: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BucketImportances {
+    pub start: f64,
+    pub end: f64,
+    pub samples: usize,
+    pub objectives: ObjectiveImportances,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pruned: BTreeMap<String, Pruned>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Importance {
     pub params: Vec<String>,
+
+    /// Whether each of `params` is categorical.
+    #[serde(default)]
+    pub categorical: Vec<bool>,
+
     pub importance: MeanAndStddev,
+
+    /// Statistics of the importance over the bootstrap replicates (see `--bootstrap`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootstrap: Option<BootstrapStats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootstrapStats {
+    pub mean: f64,
+    pub stddev: f64,
+
+    /// Lower bound of the percentile confidence interval.
+    pub lower: f64,
+
+    /// Upper bound of the percentile confidence interval.
+    pub upper: f64,
+}
+
+impl BootstrapStats {
+    fn new(xs: &mut [f64], confidence: f64) -> Self {
+        xs.sort_by_key(|&x| OrderedFloat(x));
+        let MeanAndStddev { mean, stddev } = MeanAndStddev::from_samples(xs);
+        let alpha = (1.0 - confidence) / 2.0;
+        Self {
+            mean,
+            stddev,
+            lower: quantile(xs, alpha),
+            upper: quantile(xs, 1.0 - alpha),
+        }
+    }
 }
@@ -0,0 +1,225 @@
+use super::forest::RandomForest;
+use super::rank;
+use crate::utils::MeanAndStddev;
+use anyhow::{anyhow, ensure};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+/// Method used to estimate parameter importances.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EstimatorKind {
+    /// Functional ANOVA on a random forest.
+    #[default]
+    Fanova,
+
+    /// Out-of-bag permutation importance on a random forest (first-order importances only).
+    Permutation,
+
+    /// Absolute Spearman's rank correlation (first-order importances of numerical parameters only).
+    Spearman,
+
+    /// Absolute Kendall's tau-b (first-order importances of numerical parameters only).
+    Kendall,
+}
+
+impl EstimatorKind {
+    pub const NAMES: &'static [&'static str] = &["fanova", "permutation", "spearman", "kendall"];
+
+    /// Returns the maximum number of parameters whose joint importance can be estimated.
+    ///
+    /// Joint permutation importances are not supported because they measure the total effects of
+    /// the parameters rather than their interactions.
+    pub fn max_dimension(self) -> Option<usize> {
+        match self {
+            Self::Fanova => None,
+            Self::Permutation | Self::Spearman | Self::Kendall => Some(1),
+        }
+    }
+
+    /// Returns the minimum number of samples required to fit the estimator.
+    pub fn min_samples(self) -> usize {
+        match self {
+            Self::Fanova | Self::Permutation => 2,
+            // The standard errors are not defined for less samples.
+            Self::Spearman | Self::Kendall => 3,
+        }
+    }
+
+    /// Returns `true` if the estimator can quantify the importances of categorical parameters.
+    ///
+    /// The choices have no meaningful order for the rank correlations, and ordering them by the
+    /// objective values (see `encode_params`) would inflate their correlations.
+    pub fn supports_categorical(self) -> bool {
+        !matches!(self, Self::Spearman | Self::Kendall)
+    }
+
+    /// Returns `true` if the importances are fractions of the variance of the objective.
+    ///
+    /// The importances of the other estimators are normalized so that they sum up to 1.
+    pub fn is_variance_decomposition(self) -> bool {
+        matches!(self, Self::Fanova)
+    }
+
+    /// Fits an estimator to the parameter `columns` and the objective `values`.
+    ///
+    /// If `parallel` is `true`, fANOVA fits its random forest using all the available CPUs.
+    pub fn fit(
+        self,
+        columns: &[Vec<f64>],
+        values: &[f64],
+        seed: u64,
+        parallel: bool,
+    ) -> anyhow::Result<Box<dyn Estimator>> {
+        Ok(match self {
+            Self::Fanova => {
+                let mut options = fanova::FanovaOptions::new()
+                    .random_forest(fanova::RandomForestOptions::new().seed(seed));
+                if parallel {
+                    options = options.parallel();
+                }
+                let fanova = options.fit(columns.iter().map(|c| c.as_slice()).collect(), values)?;
+                Box::new(FanovaEstimator(fanova))
+            }
+            Self::Permutation => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let forest = RandomForest::fit(&mut rng, columns, values);
+                Box::new(PermutationEstimator {
+                    rng,
+                    forest,
+                    columns: columns.to_vec(),
+                    values: values.to_vec(),
+                    variance: variance(values),
+                })
+            }
+            Self::Spearman | Self::Kendall => Box::new(RankEstimator {
+                kind: self,
+                columns: columns.to_vec(),
+                values: values.to_vec(),
+            }),
+        })
+    }
+}
+
+impl std::str::FromStr for EstimatorKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fanova" => Ok(Self::Fanova),
+            "permutation" => Ok(Self::Permutation),
+            "spearman" => Ok(Self::Spearman),
+            "kendall" => Ok(Self::Kendall),
+            _ => Err(anyhow!("unknown estimator {:?}", s)),
+        }
+    }
+}
+
+/// Error returned if the samples are too few for an estimator.
+#[derive(Debug)]
+pub struct TooFewSamples;
+
+impl std::fmt::Display for TooFewSamples {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "too few samples to estimate importances")
+    }
+}
+
+impl std::error::Error for TooFewSamples {}
+
+/// Importance estimator fitted to the samples of an objective.
+pub trait Estimator {
+    /// Quantifies the importance of the parameters at `indices`.
+    fn quantify_importance(&mut self, indices: &[usize]) -> anyhow::Result<MeanAndStddev>;
+}
+
+struct FanovaEstimator(fanova::Fanova);
+
+impl Estimator for FanovaEstimator {
+    fn quantify_importance(&mut self, indices: &[usize]) -> anyhow::Result<MeanAndStddev> {
+        let importance = self.0.quantify_importance(indices);
+        Ok(MeanAndStddev {
+            mean: importance.mean,
+            stddev: importance.stddev,
+        })
+    }
+}
+
+/// Jointly permutes the parameters at `indices`.
+///
+/// The mean and the standard deviation are taken over the trees of the forest.
+struct PermutationEstimator {
+    rng: StdRng,
+    forest: RandomForest,
+    columns: Vec<Vec<f64>>,
+    values: Vec<f64>,
+    variance: f64,
+}
+
+impl Estimator for PermutationEstimator {
+    fn quantify_importance(&mut self, indices: &[usize]) -> anyhow::Result<MeanAndStddev> {
+        let losses =
+            self.forest
+                .permutation_losses(&mut self.rng, &self.columns, &self.values, indices);
+        if losses.is_empty() {
+            // No tree has out-of-bag samples.
+            return Err(TooFewSamples.into());
+        }
+        let losses = losses
+            .into_iter()
+            .map(|loss| {
+                if self.variance > 0.0 {
+                    loss / self.variance
+                } else {
+                    0.0
+                }
+            })
+            .collect::<Vec<_>>();
+        let mean = losses.iter().sum::<f64>() / losses.len() as f64;
+        Ok(MeanAndStddev {
+            mean,
+            stddev: variance(&losses).sqrt(),
+        })
+    }
+}
+
+/// The standard deviation is the approximate standard error of the coefficient.
+struct RankEstimator {
+    kind: EstimatorKind,
+    columns: Vec<Vec<f64>>,
+    values: Vec<f64>,
+}
+
+impl Estimator for RankEstimator {
+    fn quantify_importance(&mut self, indices: &[usize]) -> anyhow::Result<MeanAndStddev> {
+        ensure!(
+            indices.len() == 1,
+            "the {:?} estimator only supports first-order importances",
+            self.kind
+        );
+        let xs = &self.columns[indices[0]];
+        let n = self.values.len();
+        if n < self.kind.min_samples() {
+            return Err(TooFewSamples.into());
+        }
+        Ok(if self.kind == EstimatorKind::Spearman {
+            let rho = rank::spearman(xs, &self.values);
+            MeanAndStddev {
+                mean: rho.abs(),
+                stddev: rank::spearman_stderr(rho, n),
+            }
+        } else {
+            MeanAndStddev {
+                mean: rank::kendall(xs, &self.values).abs(),
+                stddev: rank::kendall_stderr(n),
+            }
+        })
+    }
+}
+
+fn variance(xs: &[f64]) -> f64 {
+    let n = xs.len() as f64;
+    let mean = xs.iter().sum::<f64>() / n;
+    xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n
+}
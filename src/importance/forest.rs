@@ -0,0 +1,195 @@
+//! A minimal random forest regressor used as the surrogate model of permutation importance.
+use ordered_float::OrderedFloat;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+const TREES: usize = 64;
+const MIN_SAMPLES_SPLIT: usize = 4;
+
+#[derive(Debug)]
+pub struct RandomForest {
+    trees: Vec<(Tree, Vec<usize>)>,
+}
+
+impl RandomForest {
+    /// Fits a forest to the parameter `columns`.
+    ///
+    /// Each tree is grown on a bootstrap sample and keeps its out-of-bag sample indices.
+    pub fn fit<R: Rng>(rng: &mut R, columns: &[Vec<f64>], values: &[f64]) -> Self {
+        let n = values.len();
+        let max_features = columns.len().div_ceil(3).max(1);
+        let trees = (0..TREES)
+            .map(|_| {
+                let mut in_bag = vec![false; n];
+                let mut indices = (0..n)
+                    .map(|_| {
+                        let i = rng.gen_range(0, n);
+                        in_bag[i] = true;
+                        i
+                    })
+                    .collect::<Vec<_>>();
+                let oob = (0..n).filter(|&i| !in_bag[i]).collect();
+                let mut builder = TreeBuilder {
+                    rng: &mut *rng,
+                    columns,
+                    values,
+                    max_features,
+                    nodes: Vec::new(),
+                };
+                builder.build(&mut indices);
+                (Tree(builder.nodes), oob)
+            })
+            .collect();
+        Self { trees }
+    }
+
+    /// Returns the out-of-bag increases of the squared errors caused by jointly permuting `features`.
+    ///
+    /// Trees without out-of-bag samples are ignored.
+    pub fn permutation_losses<R: Rng>(
+        &self,
+        rng: &mut R,
+        columns: &[Vec<f64>],
+        values: &[f64],
+        features: &[usize],
+    ) -> Vec<f64> {
+        let mut row = vec![0.0; columns.len()];
+        self.trees
+            .iter()
+            .filter(|(_, oob)| !oob.is_empty())
+            .map(|(tree, oob)| {
+                let mut permuted = oob.clone();
+                permuted.shuffle(rng);
+
+                let mut base = 0.0;
+                let mut loss = 0.0;
+                for (&i, &j) in oob.iter().zip(permuted.iter()) {
+                    for (x, column) in row.iter_mut().zip(columns.iter()) {
+                        *x = column[i];
+                    }
+                    base += (tree.predict(&row) - values[i]).powi(2);
+                    for &f in features {
+                        row[f] = columns[f][j];
+                    }
+                    loss += (tree.predict(&row) - values[i]).powi(2);
+                }
+                (loss - base) / oob.len() as f64
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Leaf(f64),
+    Split {
+        feature: usize,
+        threshold: f64,
+        left: usize,
+        right: usize,
+    },
+}
+
+#[derive(Debug)]
+struct Tree(Vec<Node>);
+
+impl Tree {
+    fn predict(&self, row: &[f64]) -> f64 {
+        let mut i = 0;
+        loop {
+            match self.0[i] {
+                Node::Leaf(value) => return value,
+                Node::Split {
+                    feature,
+                    threshold,
+                    left,
+                    right,
+                } => {
+                    i = if row[feature] <= threshold {
+                        left
+                    } else {
+                        right
+                    };
+                }
+            }
+        }
+    }
+}
+
+struct TreeBuilder<'a, R> {
+    rng: &'a mut R,
+    columns: &'a [Vec<f64>],
+    values: &'a [f64],
+    max_features: usize,
+    nodes: Vec<Node>,
+}
+
+impl<'a, R: Rng> TreeBuilder<'a, R> {
+    fn build(&mut self, indices: &mut [usize]) -> usize {
+        let node = self.nodes.len();
+        let mean = indices.iter().map(|&i| self.values[i]).sum::<f64>() / indices.len() as f64;
+        self.nodes.push(Node::Leaf(mean));
+        if indices.len() < MIN_SAMPLES_SPLIT {
+            return node;
+        }
+
+        let split = match self.best_split(indices) {
+            None => return node,
+            Some(split) => split,
+        };
+        let (feature, threshold) = split;
+        indices.sort_by_key(|&i| self.columns[feature][i] > threshold);
+        let mid = indices
+            .iter()
+            .position(|&i| self.columns[feature][i] > threshold)
+            .expect("unreachable");
+        let (left_indices, right_indices) = indices.split_at_mut(mid);
+        let left = self.build(left_indices);
+        let right = self.build(right_indices);
+        self.nodes[node] = Node::Split {
+            feature,
+            threshold,
+            left,
+            right,
+        };
+        node
+    }
+
+    /// Returns the split that minimizes the sum of the squared errors of the children.
+    fn best_split(&mut self, indices: &[usize]) -> Option<(usize, f64)> {
+        let features = (0..self.columns.len()).collect::<Vec<_>>();
+        let features = features
+            .choose_multiple(self.rng, self.max_features)
+            .copied()
+            .collect::<Vec<_>>();
+
+        let total_sum = indices.iter().map(|&i| self.values[i]).sum::<f64>();
+        let n = indices.len() as f64;
+        let mut best: Option<(f64, usize, f64)> = None;
+        let mut sorted = indices.to_vec();
+        for feature in features {
+            let column = &self.columns[feature];
+            sorted.sort_by_key(|&i| OrderedFloat(column[i]));
+
+            // Minimizing the SSE is equivalent to maximizing `sum_l^2 / n_l + sum_r^2 / n_r`.
+            let mut left_sum = 0.0;
+            for (k, pair) in sorted.windows(2).enumerate() {
+                left_sum += self.values[pair[0]];
+                let (a, b) = (column[pair[0]], column[pair[1]]);
+                if a == b || a.is_nan() || b.is_nan() {
+                    continue;
+                }
+                let n_left = (k + 1) as f64;
+                let right_sum = total_sum - left_sum;
+                let score = left_sum * left_sum / n_left + right_sum * right_sum / (n - n_left);
+                if !matches!(best, Some((s, _, _)) if score <= s) {
+                    // `(a + b) / 2` may overflow or round to `b`, which leaves the right child empty.
+                    let mid = a + (b - a) / 2.0;
+                    let threshold = if mid < b { mid } else { a };
+                    best = Some((score, feature, threshold));
+                }
+            }
+        }
+        best.map(|(_, feature, threshold)| (feature, threshold))
+    }
+}
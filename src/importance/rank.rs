@@ -0,0 +1,138 @@
+//! Rank correlation coefficients between a parameter and an objective.
+use ordered_float::OrderedFloat;
+
+/// Returns the Spearman's rank correlation coefficient of `xs` and `ys`.
+pub fn spearman(xs: &[f64], ys: &[f64]) -> f64 {
+    pearson(&ranks(xs), &ranks(ys))
+}
+
+/// Returns the Kendall's tau-b of `xs` and `ys`.
+pub fn kendall(xs: &[f64], ys: &[f64]) -> f64 {
+    let mut score = 0.0;
+    let mut ties_x = 0.0;
+    let mut ties_y = 0.0;
+    let mut pairs: f64 = 0.0;
+    for i in 0..xs.len() {
+        for j in i + 1..xs.len() {
+            let dx = xs[i] - xs[j];
+            let dy = ys[i] - ys[j];
+            pairs += 1.0;
+            if dx == 0.0 {
+                ties_x += 1.0;
+            }
+            if dy == 0.0 {
+                ties_y += 1.0;
+            }
+            if dx * dy > 0.0 {
+                score += 1.0;
+            } else if dx * dy < 0.0 {
+                score -= 1.0;
+            }
+        }
+    }
+    let denominator = ((pairs - ties_x) * (pairs - ties_y)).sqrt();
+    if denominator == 0.0 {
+        0.0
+    } else {
+        score / denominator
+    }
+}
+
+/// Returns the approximate standard error of a Spearman's coefficient `rho` of `n` (> 2) samples.
+pub fn spearman_stderr(rho: f64, n: usize) -> f64 {
+    ((1.0 - rho * rho) / (n - 2) as f64).sqrt()
+}
+
+/// Returns the standard error of a Kendall's tau of `n` (> 1) samples under independence.
+pub fn kendall_stderr(n: usize) -> f64 {
+    let n = n as f64;
+    (2.0 * (2.0 * n + 5.0) / (9.0 * n * (n - 1.0))).sqrt()
+}
+
+/// Returns the (1-origin) ranks of `xs`, where ties get their average rank.
+fn ranks(xs: &[f64]) -> Vec<f64> {
+    let mut order = (0..xs.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| OrderedFloat(xs[i]));
+
+    let mut ranks = vec![0.0; xs.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && xs[order[end]] == xs[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        for &i in &order[start..end] {
+            ranks[i] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+fn pearson(xs: &[f64], ys: &[f64]) -> f64 {
+    let n = xs.len() as f64;
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = ys.iter().sum::<f64>() / n;
+    let mut cov = 0.0;
+    let mut var_x = 0.0;
+    let mut var_y = 0.0;
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    if var_x == 0.0 || var_y == 0.0 {
+        0.0
+    } else {
+        cov / (var_x * var_y).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-12,
+            "actual={}, expected={}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn ranks_average_ties() {
+        assert_eq!(ranks(&[30.0, 10.0, 20.0]), vec![3.0, 1.0, 2.0]);
+        assert_eq!(ranks(&[10.0, 20.0, 20.0, 30.0]), vec![1.0, 2.5, 2.5, 4.0]);
+        assert_eq!(ranks(&[5.0, 5.0, 5.0]), vec![2.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn spearman_works() {
+        assert_close(spearman(&[1.0, 2.0, 3.0], &[10.0, 20.0, 40.0]), 1.0);
+        assert_close(spearman(&[1.0, 2.0, 3.0], &[3.0, 2.0, 1.0]), -1.0);
+        assert_close(
+            spearman(&[1.0, 2.0, 2.0, 3.0], &[1.0, 3.0, 2.0, 4.0]),
+            4.5 / 22.5f64.sqrt(),
+        );
+        assert_close(spearman(&[1.0, 1.0, 1.0], &[1.0, 2.0, 3.0]), 0.0);
+    }
+
+    #[test]
+    fn kendall_tau_b_works() {
+        assert_close(kendall(&[1.0, 2.0, 3.0], &[10.0, 20.0, 40.0]), 1.0);
+        assert_close(kendall(&[1.0, 2.0, 3.0], &[3.0, 2.0, 1.0]), -1.0);
+
+        // 5 concordant pairs and a pair tied only in `xs` out of 6 pairs.
+        assert_close(
+            kendall(&[1.0, 2.0, 2.0, 3.0], &[1.0, 3.0, 2.0, 4.0]),
+            5.0 / 30.0f64.sqrt(),
+        );
+
+        // A pair tied in both is excluded from both sides of the denominator.
+        assert_close(kendall(&[1.0, 1.0, 2.0], &[1.0, 1.0, 2.0]), 1.0);
+        assert_close(kendall(&[1.0, 1.0, 1.0], &[1.0, 2.0, 3.0]), 0.0);
+    }
+}
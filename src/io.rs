@@ -1,36 +1,217 @@
-use anyhow::Context;
+use crate::validate::{InvalidRecord, Validator};
+use anyhow::{anyhow, bail, Context};
 use hporecord::Record;
-use std::io::BufRead;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
 
-pub fn read_records<T: BufRead>(reader: T) -> Records<T> {
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Suffixes of the files collected from directories.
+const RECORD_FILE_SUFFIXES: &[&str] = &[".jsonl", ".jsonl.gz", ".jsonl.zst"];
+
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct InputOpt {
+    /// Input JSONL files, directories or glob patterns (default: stdin).
+    ///
+    /// Directories are searched recursively for "*.jsonl", "*.jsonl.gz" and "*.jsonl.zst" files,
+    /// and "-" means the standard input.
+    /// Gzip and zstd compressed inputs are decompressed transparently.
+    pub inputs: Vec<PathBuf>,
+
+    /// Skips malformed or invalid lines (e.g., truncated writes) instead of aborting.
+    #[structopt(long)]
+    pub lenient: bool,
+
+    /// Fails if more than the given number of lines are skipped in the lenient mode.
+    #[structopt(long, requires = "lenient")]
+    pub max_errors: Option<usize>,
+}
+
+impl InputOpt {
+    pub fn sources(&self) -> anyhow::Result<Vec<Source>> {
+        if self.inputs.is_empty() {
+            return Ok(vec![Source::Stdin]);
+        }
+
+        let mut sources = Vec::new();
+        for path in &self.inputs {
+            expand_path(path, &mut sources)?;
+        }
+        Ok(sources)
+    }
+
+    pub fn read_records(&self) -> anyhow::Result<MultiRecords> {
+        Ok(self.read_sources(self.sources()?))
+    }
+
+    pub fn read_source_records(&self, source: &Source) -> anyhow::Result<MultiRecords> {
+        Ok(self.read_sources(vec![source.clone()]))
+    }
+
+    fn read_sources(&self, sources: Vec<Source>) -> MultiRecords {
+        MultiRecords {
+            sources: sources.into_iter(),
+            current: None,
+            lenient: self.lenient,
+            max_errors: self.max_errors,
+            validator: Some(Validator::default()),
+            skipped: Vec::new(),
+            finished: false,
+        }
+    }
+}
+
+fn expand_path(path: &Path, sources: &mut Vec<Source>) -> anyhow::Result<()> {
+    if path == Path::new("-") {
+        sources.push(Source::Stdin);
+    } else if path.is_dir() {
+        let mut entries = std::fs::read_dir(path)
+            .with_context(|| format!("path={:?}", path))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()
+            .with_context(|| format!("path={:?}", path))?;
+        entries.sort();
+        for entry in entries {
+            let name = match entry.file_name() {
+                Some(name) => name.to_string_lossy(),
+                None => continue,
+            };
+            if name.starts_with('.') {
+                continue;
+            }
+            if entry.is_dir() {
+                expand_path(&entry, sources)?;
+            } else if RECORD_FILE_SUFFIXES.iter().any(|s| name.ends_with(s)) {
+                sources.push(Source::File(entry));
+            }
+        }
+    } else if path.exists() {
+        sources.push(Source::File(path.to_owned()));
+    } else {
+        let pattern = path.to_string_lossy();
+        if !pattern.contains(&['*', '?', '['][..]) {
+            bail!("no such file or directory: {:?}", path);
+        }
+
+        let mut matched = false;
+        for entry in glob::glob(&pattern)? {
+            expand_path(&entry?, sources)?;
+            matched = true;
+        }
+        if !matched {
+            bail!("no files match the pattern {:?}", pattern);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Source {
+    pub fn name(&self) -> String {
+        match self {
+            Self::Stdin => "<stdin>".to_owned(),
+            Self::File(path) => path.display().to_string(),
+        }
+    }
+
+    pub fn open(&self) -> anyhow::Result<Box<dyn BufRead>> {
+        match self {
+            Self::Stdin => decode(BufReader::new(std::io::stdin())),
+            Self::File(path) => {
+                let file = File::open(path).with_context(|| format!("file={}", self.name()))?;
+                decode(BufReader::new(file))
+            }
+        }
+        .with_context(|| format!("file={}", self.name()))
+    }
+
+    pub fn read_records(&self) -> anyhow::Result<Records<Box<dyn BufRead>>> {
+        Ok(read_records(self.name(), self.open()?))
+    }
+}
+
+fn decode<T: BufRead + 'static>(mut reader: T) -> anyhow::Result<Box<dyn BufRead>> {
+    let head = reader.fill_buf()?;
+    if head.starts_with(&GZIP_MAGIC) {
+        let decoder = flate2::bufread::MultiGzDecoder::new(reader);
+        Ok(Box::new(BufReader::new(decoder)))
+    } else if head.starts_with(&ZSTD_MAGIC) {
+        let decoder = zstd::stream::read::Decoder::with_buffer(reader)?;
+        Ok(Box::new(BufReader::new(decoder)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+pub fn read_records<T: BufRead>(source: impl Into<String>, reader: T) -> Records<T> {
     Records {
+        source: source.into(),
         line_reader: reader,
         line_no: 0,
-        line: String::new(),
+        line: Vec::new(),
+        ended: false,
     }
 }
 
 #[derive(Debug)]
 pub struct Records<T> {
+    source: String,
     line_reader: T,
     line_no: usize,
-    line: String,
+    line: Vec<u8>,
+    ended: bool,
 }
 
 impl<T: BufRead> Records<T> {
     fn read_record(&mut self) -> anyhow::Result<Option<Record>> {
+        if self.ended {
+            return Ok(None);
+        }
+
         self.line.clear();
         self.line_no += 1;
-        let size = self
-            .line_reader
-            .read_line(&mut self.line)
-            .with_context(|| format!("line={}", self.line_no))?;
+        let size = match self.line_reader.read_until(b'\n', &mut self.line) {
+            Ok(size) => size,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::InvalidData
+                ) =>
+            {
+                // A truncated or corrupted (compressed) stream, e.g., left by a crashed writer.
+                // Nothing can be read after it, so it is treated as a malformed trailing line.
+                self.ended = true;
+                return Err(anyhow::Error::new(e).context(self.location()));
+            }
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("file={}, line={}", self.source, self.line_no));
+            }
+        };
         if size == 0 {
-            Ok(None)
-        } else {
-            let record = serde_json::from_str(&self.line)
-                .with_context(|| format!("line={}", self.line_no))?;
-            Ok(Some(record))
+            return Ok(None);
+        }
+
+        let record = std::str::from_utf8(&self.line)
+            .map_err(anyhow::Error::new)
+            .and_then(|line| Ok(serde_json::from_str(line)?))
+            .with_context(|| self.location())?;
+        Ok(Some(record))
+    }
+
+    fn location(&self) -> Location {
+        Location {
+            source: self.source.clone(),
+            line: self.line_no,
         }
     }
 }
@@ -42,3 +223,135 @@ impl<T: BufRead> Iterator for Records<T> {
         self.read_record().transpose()
     }
 }
+
+/// Location of a line that could not be parsed as a (valid) record.
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub source: String,
+    pub line: usize,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "file={}, line={}", self.source, self.line)
+    }
+}
+
+/// Records read from multiple sources one after another.
+pub struct MultiRecords {
+    sources: std::vec::IntoIter<Source>,
+    current: Option<Records<Box<dyn BufRead>>>,
+    lenient: bool,
+    max_errors: Option<usize>,
+    validator: Option<Validator>,
+    skipped: Vec<(Location, String)>,
+    finished: bool,
+}
+
+impl MultiRecords {
+    /// Disables the validation of records against their study definitions.
+    pub fn unvalidated(mut self) -> Self {
+        self.validator = None;
+        self
+    }
+
+    /// Returns the location of the most recently read line.
+    pub fn location(&self) -> Option<Location> {
+        self.current.as_ref().map(|records| records.location())
+    }
+
+    fn validate(&mut self, record: &Record) -> anyhow::Result<()> {
+        let violations = if let Some(validator) = &mut self.validator {
+            validator.validate(record)
+        } else {
+            return Ok(());
+        };
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        let location = self.location().expect("unreachable");
+        Err(anyhow::Error::new(InvalidRecord { violations }).context(location))
+    }
+
+    fn skip(&mut self, e: anyhow::Error) -> anyhow::Result<()> {
+        let location = match e.downcast_ref::<Location>() {
+            Some(location) if self.lenient => location.clone(),
+            _ => return Err(e),
+        };
+        self.skipped.push((location, e.root_cause().to_string()));
+
+        if let Some(max) = self.max_errors {
+            if self.skipped.len() > max {
+                return Err(anyhow!(
+                    "too many malformed or invalid lines (more than {}); the last one was at {}",
+                    max,
+                    self.skipped[self.skipped.len() - 1].0
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn report_skipped(&self) {
+        const MAX_REPORTS: usize = 10;
+
+        if self.skipped.is_empty() {
+            return;
+        }
+
+        eprintln!(
+            "[WARN] Skipped {} malformed or invalid lines:",
+            self.skipped.len()
+        );
+        for (location, reason) in self.skipped.iter().take(MAX_REPORTS) {
+            eprintln!("  - {}: {}", location, reason);
+        }
+        if self.skipped.len() > MAX_REPORTS {
+            eprintln!("  - ... and {} more", self.skipped.len() - MAX_REPORTS);
+        }
+    }
+}
+
+impl Iterator for MultiRecords {
+    type Item = anyhow::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(records) = &mut self.current {
+                match records.next() {
+                    Some(Ok(record)) => match self.validate(&record) {
+                        Ok(()) => return Some(Ok(record)),
+                        Err(e) => {
+                            if let Err(e) = self.skip(e) {
+                                return Some(Err(e));
+                            }
+                            continue;
+                        }
+                    },
+                    Some(Err(e)) => {
+                        if let Err(e) = self.skip(e) {
+                            return Some(Err(e));
+                        }
+                        continue;
+                    }
+                    None => self.current = None,
+                }
+            }
+
+            let source = if let Some(source) = self.sources.next() {
+                source
+            } else {
+                if !self.finished {
+                    self.finished = true;
+                    self.report_skipped();
+                }
+                return None;
+            };
+            match source.read_records() {
+                Ok(records) => self.current = Some(records),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
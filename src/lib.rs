@@ -1,6 +1,13 @@
 pub mod convert;
 pub mod curve;
+pub mod filter;
 pub mod importance;
 pub mod io;
+pub mod merge;
+pub mod parallel;
 pub mod plot;
+pub mod progress;
+pub mod scale;
+pub mod summary;
 pub mod utils;
+pub mod validate;
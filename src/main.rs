@@ -7,22 +7,24 @@ enum Opt {
     Curve(reveal::curve::CurveOpt),
     Plot(reveal::plot::PlotOpt),
     Convert(reveal::convert::ConvertOpt),
+    Filter(reveal::filter::FilterOpt),
+    Merge(reveal::merge::MergeOpt),
+    Summary(reveal::summary::SummaryOpt),
+    Validate(reveal::validate::ValidateOpt),
 }
 
 fn main() -> anyhow::Result<()> {
     let opt = Opt::from_args();
     match opt {
         Opt::Importance(opt) => {
-            let records = reveal::io::read_records(std::io::stdin().lock())
-                .collect::<anyhow::Result<Vec<_>>>()?;
-            let importances = opt.calculate_importances(&records)?;
+            let records = opt.input.read_records()?;
+            let importances = opt.calculate_importances(records)?;
             serde_json::to_writer(std::io::stdout().lock(), &importances)?;
             println!();
         }
         Opt::Curve(opt) => {
-            let records = reveal::io::read_records(std::io::stdin().lock())
-                .collect::<anyhow::Result<Vec<_>>>()?;
-            let result = opt.calculate_optimization_curve(&records)?;
+            let records = opt.input.read_records()?;
+            let result = opt.calculate_optimization_curve(records)?;
             serde_json::to_writer(std::io::stdout().lock(), &result)?;
             println!();
         }
@@ -30,9 +32,24 @@ fn main() -> anyhow::Result<()> {
             opt.plot(std::io::stdin().lock())?;
         }
         Opt::Convert(opt) => {
-            let records = reveal::io::read_records(std::io::stdin().lock())
-                .collect::<anyhow::Result<Vec<_>>>()?;
-            opt.convert(&records)?;
+            opt.convert()?;
+        }
+        Opt::Filter(opt) => {
+            let records = opt.input.read_records()?;
+            let stdout = std::io::stdout();
+            opt.filter(records, std::io::BufWriter::new(stdout.lock()))?;
+        }
+        Opt::Merge(opt) => {
+            let stdout = std::io::stdout();
+            opt.merge(std::io::BufWriter::new(stdout.lock()))?;
+        }
+        Opt::Summary(opt) => {
+            let records = opt.input.read_records()?;
+            let summaries = opt.summarize(records)?;
+            opt.write(std::io::stdout().lock(), &summaries)?;
+        }
+        Opt::Validate(opt) => {
+            opt.validate(std::io::stdout().lock())?;
         }
     }
     Ok(())
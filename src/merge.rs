@@ -0,0 +1,136 @@
+use crate::io::InputOpt;
+use crate::utils::diff_study_defs;
+use anyhow::bail;
+use hporecord::{Record, StudyId, StudyRecord};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct MergeOpt {
+    #[structopt(flatten)]
+    pub input: InputOpt,
+
+    /// Renames colliding studies (e.g., `foo` to `foo#1`) instead of failing.
+    ///
+    /// Studies sharing the same ID are deduplicated if their definitions are the same.
+    #[structopt(long)]
+    pub rename: bool,
+}
+
+impl MergeOpt {
+    pub fn merge<W: Write>(&self, mut writer: W) -> anyhow::Result<()> {
+        let mut studies: BTreeMap<StudyId, (String, StudyRecord)> = BTreeMap::new();
+        let mut stats = Stats::default();
+        for source in self.input.sources()? {
+            let source_name = source.name();
+            let mut id_mapping = BTreeMap::new();
+            let mut skipped_studies = BTreeSet::new();
+            for record in self.input.read_source_records(&source)? {
+                match record? {
+                    Record::Study(mut study) => {
+                        stats.studies += 1;
+                        let original_id = study.id.clone();
+                        match self.resolve_id(&studies, &source_name, &study)? {
+                            Resolved::Duplicate(id) => {
+                                stats.duplicates += 1;
+                                id_mapping.insert(original_id, id);
+                            }
+                            Resolved::New(id) => {
+                                if id != original_id {
+                                    eprintln!(
+                                        "[WARN] Renamed the study {:?} in {} to {:?}",
+                                        original_id, source_name, id
+                                    );
+                                    stats.renamed += 1;
+                                }
+                                study.id = id.clone();
+                                write_record(&mut writer, &Record::Study(study.clone()))?;
+                                studies.insert(id.clone(), (source_name.clone(), study));
+                                id_mapping.insert(original_id, id);
+                            }
+                        }
+                    }
+                    Record::Eval(mut eval) => {
+                        if let Some(id) = id_mapping.get(&eval.study) {
+                            eval.study = id.clone();
+                            write_record(&mut writer, &Record::Eval(eval))?;
+                            stats.evals += 1;
+                        } else if !skipped_studies.contains(&eval.study) {
+                            eprintln!("[WARN] Unknown study in {}: {:?}", source_name, eval.study);
+                            skipped_studies.insert(eval.study);
+                        }
+                    }
+                }
+            }
+        }
+        writer.flush()?;
+
+        eprintln!(
+            "Merged {} studies ({} duplicated, {} renamed) and {} evaluations",
+            stats.studies, stats.duplicates, stats.renamed, stats.evals
+        );
+        Ok(())
+    }
+
+    fn resolve_id(
+        &self,
+        studies: &BTreeMap<StudyId, (String, StudyRecord)>,
+        source_name: &str,
+        study: &StudyRecord,
+    ) -> anyhow::Result<Resolved> {
+        let (first_source_name, first) = if let Some(first) = studies.get(&study.id) {
+            first
+        } else {
+            return Ok(Resolved::New(study.id.clone()));
+        };
+
+        let diffs = diff_study_defs(first, study)?;
+        if diffs.is_empty() {
+            return Ok(Resolved::Duplicate(study.id.clone()));
+        }
+
+        if !self.rename {
+            bail!(
+                "conflicting definitions of the study {:?} ({} vs {}):\n  {}",
+                study.id,
+                first_source_name,
+                source_name,
+                diffs.join("\n  ")
+            );
+        }
+
+        for i in 1.. {
+            let id = format!("{}#{}", study.id, i);
+            match studies.get(&id) {
+                None => return Ok(Resolved::New(id)),
+                Some((_, renamed)) if diff_study_defs(renamed, study)?.is_empty() => {
+                    return Ok(Resolved::Duplicate(id));
+                }
+                Some(_) => {}
+            }
+        }
+        unreachable!()
+    }
+}
+
+#[derive(Debug)]
+enum Resolved {
+    New(StudyId),
+    Duplicate(StudyId),
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    studies: usize,
+    duplicates: usize,
+    renamed: usize,
+    evals: usize,
+}
+
+fn write_record<W: Write>(mut writer: W, record: &Record) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut writer, record)?;
+    writeln!(writer)?;
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ParallelOpt {
+    /// Number of threads used to process studies (default: the number of available CPUs).
+    #[structopt(long)]
+    pub jobs: Option<NonZeroUsize>,
+}
+
+impl ParallelOpt {
+    pub fn jobs(&self) -> usize {
+        self.jobs
+            .or_else(|| std::thread::available_parallelism().ok())
+            .map_or(1, |n| n.get())
+    }
+
+    /// Applies `f` to each of `items` in parallel and returns the results in the order of `items`.
+    pub fn map<T, U, F>(&self, items: &[T], f: F) -> Vec<U>
+    where
+        T: Sync,
+        U: Send,
+        F: Fn(&T) -> U + Sync,
+    {
+        let jobs = self.jobs().min(items.len());
+        if jobs <= 1 {
+            return items.iter().map(f).collect();
+        }
+
+        let next = AtomicUsize::new(0);
+        let mut results = std::thread::scope(|scope| {
+            let handles = (0..jobs)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut results = Vec::new();
+                        loop {
+                            let i = next.fetch_add(1, Ordering::SeqCst);
+                            if i >= items.len() {
+                                break;
+                            }
+                            results.push((i, f(&items[i])));
+                        }
+                        results
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("a worker thread panicked"))
+                .collect::<Vec<_>>()
+        });
+        results.sort_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
@@ -1,4 +1,4 @@
-use crate::curve::{Studies, Study};
+use crate::curve::{Band, Studies, Study};
 use crate::plot::utils;
 use ordered_float::OrderedFloat;
 use std::borrow::Cow;
@@ -43,17 +43,47 @@ pub struct PlotCurveOpt {
     #[structopt(long)]
     pub ylogscale: bool,
 
-    /// Displays errorbar showing standard deviation of optimization curve.
+    /// Makes X axis log scale (suitable for the curves resampled with `--log-grid`).
+    #[structopt(long)]
+    pub xlogscale: bool,
+
+    /// Displays errorbar showing the band of optimization curve (e.g., standard deviation).
     #[structopt(long)]
     pub errorbar: bool,
+
+    /// Displays the number of the seeds aggregated at each point on the second Y axis.
+    #[structopt(long)]
+    pub sample_count: bool,
 }
 
 impl PlotCurveOpt {
     pub fn plot(&self, reader: impl std::io::BufRead) -> anyhow::Result<()> {
-        let studies: Studies = serde_json::from_reader(reader)?;
+        let mut studies: Studies = serde_json::from_reader(reader)?;
         std::fs::create_dir_all(&self.out)?;
 
+        for study in studies.values_mut().flat_map(|s| s.values_mut()) {
+            if study.band.is_none() {
+                study.band = Some(Band::from_mean_and_stddev(&study.best_values_avg));
+            }
+            if study.xs.is_empty() {
+                // Older versions resampled the curves at the integer span values.
+                study.xs = (0..band(study).center.len()).map(|i| i as f64).collect();
+            }
+            if study.sample_counts.is_empty() {
+                let samples = study.samples;
+                study.sample_counts = band(study)
+                    .center
+                    .iter()
+                    .map(|v| if v.is_some() { samples } else { 0 })
+                    .collect();
+            }
+        }
+
         for (problem_id, studies) in studies {
+            if x_len(&studies) == 0 {
+                eprintln!("[WARN] Skipped the problem {:?} (empty curve)", problem_id);
+                continue;
+            }
             self.plot_curve(&problem_id, &studies)?;
         }
 
@@ -117,6 +147,9 @@ impl PlotCurveOpt {
         if self.ylogscale {
             writeln!(w, "set logscale y")?;
         }
+        if self.xlogscale {
+            writeln!(w, "set logscale x")?;
+        }
 
         writeln!(
             w,
@@ -128,6 +161,18 @@ impl PlotCurveOpt {
         if self.errorbar {
             writeln!(w, "set style fill transparent solid 0.2")?;
             writeln!(w, "set style fill noborder")?;
+            writeln!(
+                w,
+                "set key title {:?}",
+                band(studies.values().next().expect("unreachable")).label
+            )?;
+        }
+
+        if self.sample_count {
+            writeln!(w, "set y2label \"samples\"")?;
+            writeln!(w, "set y2range [0:]")?;
+            writeln!(w, "set y2tics")?;
+            writeln!(w, "set ytics nomirror")?;
         }
 
         write!(
@@ -145,15 +190,21 @@ impl PlotCurveOpt {
             } else {
                 write!(w, ", \"\"")?;
             }
-            write!(w, " u ($0):{} w l t columnhead lc {}", (i * 2) + 1, i + 1)?;
+            write!(w, " u 1:{} w l t columnhead lc {}", (i * 4) + 2, i + 1)?;
             if self.errorbar {
                 write!(
                     w,
-                    ", \"\" u ($0 - 1):(${}-${}):(${}+${}) with filledcurves notitle lc {}",
-                    (i * 2) + 1,
-                    (i * 2) + 1 + 1,
-                    (i * 2) + 1,
-                    (i * 2) + 1 + 1,
+                    ", \"\" u 1:{}:{} with filledcurves notitle lc {}",
+                    (i * 4) + 2 + 1,
+                    (i * 4) + 2 + 2,
+                    i + 1
+                )?;
+            }
+            if self.sample_count {
+                write!(
+                    w,
+                    ", \"\" u 1:{} axes x1y2 w steps dt 2 notitle lc {}",
+                    (i * 4) + 2 + 3,
                     i + 1
                 )?;
             }
@@ -171,23 +222,36 @@ impl PlotCurveOpt {
         let file = std::fs::File::create(dat_path)?;
         let mut w = std::io::BufWriter::new(file);
 
+        let first = studies.values().next().expect("unreachable");
+        write!(w, "{:?} ", first.span_name)?;
         for (optimizer, study) in studies {
             let name = format!("{} (n={})", optimizer, study.samples);
-            write!(w, "{:?} {:?} ", name, name)?;
+            write!(w, "{:?} {:?} {:?} {:?} ", name, name, name, name)?;
         }
         writeln!(w)?;
 
-        let size = x_len(studies);
-        for i in 0..size {
+        // The optimizers of a problem share the same grid, so the longest curve has all the points.
+        let longest = studies
+            .values()
+            .max_by_key(|study| study.xs.len())
+            .expect("unreachable");
+        for i in 0..x_len(studies) {
+            write!(w, "{} ", longest.xs[i])?;
             for study in studies.values() {
-                write!(
-                    w,
-                    "{} {} ",
-                    study.best_values_avg.mean[i]
-                        .map_or(Cow::Borrowed("NaN"), |v| Cow::Owned(v.to_string())),
-                    study.best_values_avg.stddev[i]
-                        .map_or(Cow::Borrowed("NaN"), |v| Cow::Owned(v.to_string()))
-                )?;
+                let band = band(study);
+                let count = study.sample_counts.get(i).map(|&c| c as f64);
+                for v in [
+                    at(&band.center, i),
+                    at(&band.lower, i),
+                    at(&band.upper, i),
+                    count,
+                ] {
+                    write!(
+                        w,
+                        "{} ",
+                        v.map_or(Cow::Borrowed("NaN"), |v| Cow::Owned(v.to_string()))
+                    )?;
+                }
             }
             writeln!(w)?;
         }
@@ -210,11 +274,7 @@ impl PlotCurveOpt {
             let max_step = x_len(studies) - 1;
             let step = max_step / 5;
 
-            if let Some(y) = mean(
-                studies
-                    .values()
-                    .filter_map(|s| s.best_values_avg.mean[step]),
-            ) {
+            if let Some(y) = mean(studies.values().filter_map(|s| at(&band(s).center, step))) {
                 let ymax = y.to_string();
                 if ymax == self.ymin(studies) {
                     "".to_string()
@@ -233,12 +293,9 @@ impl PlotCurveOpt {
             if let Some(y) = studies
                 .values()
                 .filter_map(|s| {
-                    Some(OrderedFloat(
-                        s.best_values_avg.mean[i].expect("unreachable")
-                            + (s.best_values_avg.mean[i].expect("unreachable")
-                                - s.best_values_avg.mean[j].expect("unreachable"))
-                                * 0.1,
-                    ))
+                    let center = &band(s).center;
+                    let (yi, yj) = (at(center, i)?, at(center, j)?);
+                    Some(OrderedFloat(yi + (yi - yj) * 0.1))
                 })
                 .max()
             {
@@ -270,12 +327,9 @@ impl PlotCurveOpt {
             if let Some(y) = studies
                 .values()
                 .filter_map(|s| {
-                    Some(OrderedFloat(
-                        s.best_values_avg.mean[i].expect("unreachable")
-                            + (s.best_values_avg.mean[i].expect("unreachable")
-                                - s.best_values_avg.mean[j].expect("unreachable"))
-                                * 0.1,
-                    ))
+                    let center = &band(s).center;
+                    let (yi, yj) = (at(center, i)?, at(center, j)?);
+                    Some(OrderedFloat(yi + (yi - yj) * 0.1))
                 })
                 .min()
             {
@@ -287,11 +341,7 @@ impl PlotCurveOpt {
             let max_step = x_len(studies) - 1;
             let step = max_step / 5;
 
-            if let Some(y) = mean(
-                studies
-                    .values()
-                    .filter_map(|s| s.best_values_avg.mean[step]),
-            ) {
+            if let Some(y) = mean(studies.values().filter_map(|s| at(&band(s).center, step))) {
                 let ymin = y.to_string();
                 if ymin == self.ymax(studies) {
                     "".to_string()
@@ -320,11 +370,20 @@ impl PlotCurveOpt {
 fn x_len(studies: &BTreeMap<String, Study>) -> usize {
     studies
         .values()
-        .map(|study| study.best_values_avg.mean.len())
-        .min()
+        .map(|study| band(study).center.len().min(study.xs.len()))
+        .max()
         .expect("unreachable")
 }
 
+/// Returns the `i`-th value of a curve, or `None` if the curve has ended before it.
+fn at(values: &[Option<f64>], i: usize) -> Option<f64> {
+    values.get(i).copied().flatten()
+}
+
+fn band(study: &Study) -> &Band {
+    study.band.as_ref().expect("unreachable")
+}
+
 fn mean(xs: impl Iterator<Item = f64>) -> Option<f64> {
     let mut v = 0.0;
     let mut n = 0;
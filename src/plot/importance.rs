@@ -1,7 +1,11 @@
-use crate::importance::{Importance, Importances};
-use crate::plot::utils::execute_gnuplot;
+use crate::importance::{
+    BucketImportances, Importance, ImportanceReport, Importances, StudyImportances,
+};
+use crate::plot::utils::{execute_gnuplot, normalize_filename};
+use hporecord::StudyId;
+use serde::Deserialize;
 use serde_json;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::Write;
 use structopt::StructOpt;
 
@@ -9,17 +13,50 @@ use structopt::StructOpt;
 #[structopt(rename_all = "kebab-case")]
 pub enum PlotImportanceOpt {
     StackedBar(PlotStackedBarOpt),
+    Bar(PlotBarOpt),
+    TimeSeries(PlotTimeSeriesOpt),
 }
 
 impl PlotImportanceOpt {
     pub fn plot(&self, reader: impl std::io::BufRead) -> anyhow::Result<()> {
-        let importances: Importances = serde_json::from_reader(reader)?;
+        let importances = match serde_json::from_reader(reader)? {
+            ImportanceOutput::Report(report) => report.importances,
+            ImportanceOutput::Legacy(importances) => importances
+                .into_iter()
+                .map(|(study_id, importances)| {
+                    let study = StudyImportances {
+                        objectives: std::iter::once((LEGACY_OBJECTIVE.to_owned(), importances))
+                            .collect(),
+                        buckets: Vec::new(),
+                        studies: None,
+                        pruned: BTreeMap::new(),
+                        inactive_params: BTreeMap::new(),
+                    };
+                    (study_id, study)
+                })
+                .collect(),
+        };
         match self {
             Self::StackedBar(opt) => opt.plot(importances),
+            Self::Bar(opt) => opt.plot(importances),
+            Self::TimeSeries(opt) => opt.plot(importances),
         }
     }
 }
 
+/// Objective name given to the importances in the outputs of older versions.
+const LEGACY_OBJECTIVE: &str = "objective";
+
+/// Output of `importance`, including that of older versions.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ImportanceOutput {
+    Report(ImportanceReport),
+
+    /// Importances of a single objective keyed by study (older versions).
+    Legacy(BTreeMap<StudyId, Vec<Importance>>),
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 pub struct PlotStackedBarOpt {
@@ -40,22 +77,26 @@ pub struct PlotStackedBarOpt {
 
 impl PlotStackedBarOpt {
     pub fn plot(&self, importances: Importances) -> anyhow::Result<()> {
-        let mut groups = BTreeMap::<_, Vec<_>>::new();
-        for (study_id, importances) in &importances {
-            let mut key = importances
-                .iter()
-                .map(|im| im.params.as_slice())
-                .collect::<Vec<_>>();
-            key.sort();
-            groups
-                .entry(key)
-                .or_default()
-                .push((study_id.as_str(), importances.as_slice()));
+        let mut groups = BTreeMap::<_, BTreeMap<_, Vec<_>>>::new();
+        for (study_id, study) in &importances {
+            for (objective, importances) in &study.objectives {
+                let mut key = importances
+                    .iter()
+                    .map(|im| im.params.as_slice())
+                    .collect::<Vec<_>>();
+                key.sort();
+                groups
+                    .entry(key)
+                    .or_default()
+                    .entry(objective.as_str())
+                    .or_default()
+                    .push((study_id.as_str(), importances.as_slice()));
+            }
         }
 
         std::fs::create_dir_all(&self.out)?;
-        for (i, (key, importances)) in groups.into_iter().enumerate() {
-            self.plot_stacked_bar(i, &key, &importances)?;
+        for (i, (key, panels)) in groups.into_iter().enumerate() {
+            self.plot_stacked_bar(i, &key, &panels)?;
         }
         Ok(())
     }
@@ -71,7 +112,12 @@ impl PlotStackedBarOpt {
 
         write!(writer, "Study")?;
         for params in params_list {
-            write!(writer, " {:?}", params.join("&"))?;
+            let label = studies_list
+                .iter()
+                .flat_map(|(_, importances)| importances.iter())
+                .find(|im| im.params == *params)
+                .map_or_else(|| params.join("&"), label);
+            write!(writer, " {:?}", label)?;
         }
         writeln!(writer)?;
 
@@ -95,14 +141,13 @@ impl PlotStackedBarOpt {
     fn make_gnuplot_script(
         &self,
         path: &std::path::PathBuf,
-        data_path: &std::path::PathBuf,
+        panels: &[(&str, std::path::PathBuf)],
         png_path: &std::path::PathBuf,
         x_count: usize,
     ) -> anyhow::Result<()> {
         let file = std::fs::File::create(path)?;
         let mut writer = std::io::BufWriter::new(file);
 
-        writeln!(writer, "set title \"Parameter Importance\"")?;
         writeln!(writer, "set key invert reverse Left outside")?;
         writeln!(writer, "set key autotitle columnheader")?;
         writeln!(writer, "set yrange [0:1]")?;
@@ -116,14 +161,24 @@ impl PlotStackedBarOpt {
         writeln!(
             writer,
             "set terminal pngcairo size {},{} noenhanced",
-            self.width, self.height
+            self.width,
+            self.height * panels.len()
         )?;
         writeln!(writer, "set output {:?};", png_path)?;
-        write!(writer, "plot {:?} using 2:xtic(1)", data_path)?;
-        if x_count > 1 {
-            write!(writer, ", for [i=3:{}] '' using i", 3 + (x_count - 2))?;
+        if panels.len() > 1 {
+            writeln!(writer, "set multiplot layout {},1", panels.len())?;
+        }
+        for (objective, data_path) in panels {
+            writeln!(writer, "set title \"Parameter Importance ({})\"", objective)?;
+            write!(writer, "plot {:?} using 2:xtic(1)", data_path)?;
+            if x_count > 1 {
+                write!(writer, ", for [i=3:{}] '' using i", 3 + (x_count - 2))?;
+            }
+            writeln!(writer)?;
+        }
+        if panels.len() > 1 {
+            writeln!(writer, "unset multiplot")?;
         }
-        writeln!(writer)?;
 
         Ok(())
     }
@@ -132,23 +187,129 @@ impl PlotStackedBarOpt {
         &self,
         number: usize,
         params_list: &[&[String]],
-        studies_list: &[(&str, &[Importance])],
+        panels: &BTreeMap<&str, Vec<(&str, &[Importance])>>,
     ) -> anyhow::Result<()> {
-        let data_file_path = self.out.join(format!("{}.dat", number));
         let script_file_path = self.out.join(format!("{}.gp", number));
         let png_file_path = self.out.join(format!("{}.png", number));
 
-        self.generate_data_file(&data_file_path, params_list, studies_list)?;
+        let mut data_files = Vec::new();
+        for (i, (objective, studies_list)) in panels.iter().enumerate() {
+            let data_file_path = self.out.join(format!("{}-{}.dat", number, i));
+            self.generate_data_file(&data_file_path, params_list, studies_list)?;
+            data_files.push((*objective, data_file_path));
+        }
 
         self.make_gnuplot_script(
             &script_file_path,
-            &data_file_path,
+            &data_files,
             &png_file_path,
             params_list.len(),
         )?;
 
         execute_gnuplot(&script_file_path)?;
 
+        if !self.retain_temp_file {
+            for (_, data_file_path) in data_files {
+                std::fs::remove_file(data_file_path)?;
+            }
+            std::fs::remove_file(script_file_path)?;
+        }
+
+        eprintln!("Generated: {:?}", png_file_path);
+
+        Ok(())
+    }
+}
+
+/// Plots importances of each study and objective as a bar chart with error bars.
+///
+/// The error bars are the bootstrap confidence intervals (see `importance --bootstrap`) if available,
+/// and otherwise the means plus or minus the standard deviations.
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct PlotBarOpt {
+    #[structopt(long, default_value = "plot-results/importance-bar/")]
+    pub out: std::path::PathBuf,
+
+    /// Image width in pixels.
+    #[structopt(long, default_value = "800")]
+    pub width: usize,
+
+    /// Image height in pixels.
+    #[structopt(long, default_value = "600")]
+    pub height: usize,
+
+    #[structopt(long)]
+    pub retain_temp_file: bool,
+}
+
+impl PlotBarOpt {
+    pub fn plot(&self, importances: Importances) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.out)?;
+        for (study_id, study) in &importances {
+            for (objective, importances) in &study.objectives {
+                self.plot_bar(study_id, objective, importances)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn plot_bar(
+        &self,
+        study_id: &str,
+        objective: &str,
+        importances: &[Importance],
+    ) -> anyhow::Result<()> {
+        let filename_stem = format!(
+            "{}-{}",
+            normalize_filename(study_id),
+            normalize_filename(objective)
+        );
+        let data_file_path = self.out.join(format!("{}.dat", filename_stem));
+        let script_file_path = self.out.join(format!("{}.gp", filename_stem));
+        let png_file_path = self.out.join(format!("{}.png", filename_stem));
+
+        {
+            let file = std::fs::File::create(&data_file_path)?;
+            let mut writer = std::io::BufWriter::new(file);
+            writeln!(writer, "# Params Mean Lower Upper")?;
+            for im in importances {
+                let mean = im.importance.mean;
+                let (lower, upper) = if let Some(b) = &im.bootstrap {
+                    (b.lower, b.upper)
+                } else {
+                    (mean - im.importance.stddev, mean + im.importance.stddev)
+                };
+                writeln!(writer, "{:?} {} {} {}", label(im), mean, lower, upper)?;
+            }
+        }
+
+        {
+            let file = std::fs::File::create(&script_file_path)?;
+            let mut writer = std::io::BufWriter::new(file);
+            writeln!(
+                writer,
+                "set title \"Parameter Importance: {} ({})\"",
+                study_id, objective
+            )?;
+            writeln!(writer, "set ylabel \"Importance\"")?;
+            writeln!(writer, "unset key")?;
+            writeln!(writer, "set xtics nomirror rotate by -45 scale 0")?;
+            writeln!(writer, "set style data histogram")?;
+            writeln!(writer, "set style histogram errorbars gap 1 lw 1")?;
+            writeln!(writer, "set style fill solid border -1")?;
+            writeln!(writer, "set boxwidth 0.8")?;
+            writeln!(
+                writer,
+                "set terminal pngcairo size {},{} noenhanced",
+                self.width, self.height
+            )?;
+            writeln!(writer, "set output {:?}", png_file_path)?;
+            writeln!(writer, "plot {:?} using 2:3:4:xtic(1)", data_file_path)?;
+        }
+
+        execute_gnuplot(&script_file_path)?;
+
         if !self.retain_temp_file {
             std::fs::remove_file(data_file_path)?;
             std::fs::remove_file(script_file_path)?;
@@ -159,3 +320,142 @@ impl PlotStackedBarOpt {
         Ok(())
     }
 }
+
+/// Plots importances per budget bucket (see `importance --bucket-width`) as line charts.
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct PlotTimeSeriesOpt {
+    #[structopt(long, default_value = "plot-results/importance-time-series/")]
+    pub out: std::path::PathBuf,
+
+    /// Image width in pixels.
+    #[structopt(long, default_value = "800")]
+    pub width: usize,
+
+    /// Image height in pixels.
+    #[structopt(long, default_value = "600")]
+    pub height: usize,
+
+    #[structopt(long)]
+    pub retain_temp_file: bool,
+}
+
+impl PlotTimeSeriesOpt {
+    pub fn plot(&self, importances: Importances) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.out)?;
+        for (study_id, study) in &importances {
+            let objectives = study
+                .buckets
+                .iter()
+                .flat_map(|b| b.objectives.keys())
+                .collect::<BTreeSet<_>>();
+            for objective in objectives {
+                self.plot_time_series(study_id, objective, &study.buckets)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn plot_time_series(
+        &self,
+        study_id: &str,
+        objective: &str,
+        buckets: &[BucketImportances],
+    ) -> anyhow::Result<()> {
+        let filename_stem = format!(
+            "{}-{}",
+            normalize_filename(study_id),
+            normalize_filename(objective)
+        );
+        let data_file_path = self.out.join(format!("{}.dat", filename_stem));
+        let script_file_path = self.out.join(format!("{}.gp", filename_stem));
+        let png_file_path = self.out.join(format!("{}.png", filename_stem));
+
+        let mut labels = Vec::new();
+        for bucket in buckets {
+            for im in bucket.objectives.get(objective).into_iter().flatten() {
+                let label = label(im);
+                if !labels.contains(&label) {
+                    labels.push(label);
+                }
+            }
+        }
+
+        {
+            let file = std::fs::File::create(&data_file_path)?;
+            let mut writer = std::io::BufWriter::new(file);
+            write!(writer, "Bucket")?;
+            for label in &labels {
+                write!(writer, " {:?}", label)?;
+            }
+            writeln!(writer)?;
+
+            for bucket in buckets {
+                write!(writer, "{}", bucket.start)?;
+                let importances = bucket.objectives.get(objective);
+                for l in &labels {
+                    let importance = importances
+                        .and_then(|ims| ims.iter().find(|im| label(im) == *l))
+                        .map_or_else(|| "NaN".to_owned(), |im| im.importance.mean.to_string());
+                    write!(writer, " {}", importance)?;
+                }
+                writeln!(writer)?;
+            }
+        }
+
+        {
+            let file = std::fs::File::create(&script_file_path)?;
+            let mut writer = std::io::BufWriter::new(file);
+            writeln!(
+                writer,
+                "set title \"Parameter Importance over Budget: {} ({})\"",
+                study_id, objective
+            )?;
+            writeln!(writer, "set xlabel \"Bucket Start\"")?;
+            writeln!(writer, "set ylabel \"Importance\"")?;
+            writeln!(writer, "set key outside")?;
+            writeln!(writer, "set key autotitle columnheader")?;
+            writeln!(writer, "set datafile missing \"NaN\"")?;
+            writeln!(
+                writer,
+                "set terminal pngcairo size {},{} noenhanced",
+                self.width, self.height
+            )?;
+            writeln!(writer, "set output {:?}", png_file_path)?;
+            writeln!(
+                writer,
+                "plot for [i=2:{}] {:?} using 1:i with linespoints",
+                labels.len() + 1,
+                data_file_path
+            )?;
+        }
+
+        execute_gnuplot(&script_file_path)?;
+
+        if !self.retain_temp_file {
+            std::fs::remove_file(data_file_path)?;
+            std::fs::remove_file(script_file_path)?;
+        }
+
+        eprintln!("Generated: {:?}", png_file_path);
+
+        Ok(())
+    }
+}
+
+/// Makes the legend label of an importance entry, marking categorical parameters with `(cat)`.
+fn label(importance: &Importance) -> String {
+    importance
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| {
+            if importance.categorical.get(i).copied().unwrap_or(false) {
+                format!("{} (cat)", param)
+            } else {
+                param.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
@@ -0,0 +1,38 @@
+//! Progress bars drawn on the standard error.
+//!
+//! `indicatif` does not draw anything if the standard error is not a terminal,
+//! so the bars are automatically disabled when it is redirected.
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Makes a spinner which counts the loaded records.
+pub fn record_spinner() -> ProgressBar {
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner} Loading records: {pos} ({per_sec}, {elapsed})"),
+    );
+    pb
+}
+
+/// Makes a bar which counts the processed studies.
+///
+/// The prefix and the message are expected to be the current study and its status, respectively.
+pub fn study_bar(studies: usize) -> ProgressBar {
+    let pb = ProgressBar::new(studies as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] {bar:40} {pos}/{len} studies {prefix}: {wide_msg}"),
+    );
+    pb
+}
+
+/// Prints a line to the standard error without breaking the drawing of `pb`.
+///
+/// Unlike `ProgressBar::println`, the line is printed even if the bar is hidden.
+pub fn println(pb: &ProgressBar, line: impl Into<String>) {
+    if pb.is_hidden() {
+        eprintln!("{}", line.into());
+    } else {
+        pb.println(line);
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::ensure;
+use hporecord::{ParamDef, ParamRange, Scale};
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ScaleOpt {
+    /// Ignores the log scale declared in parameter definitions.
+    #[structopt(long)]
+    pub no_auto_log_scale: bool,
+
+    /// Log-transforms the given parameter regardless of its definition.
+    #[structopt(long = "log-param", number_of_values = 1)]
+    pub log_params: Vec<String>,
+
+    /// Does not log-transform the given parameter regardless of its definition.
+    #[structopt(long = "linear-param", number_of_values = 1)]
+    pub linear_params: Vec<String>,
+}
+
+impl ScaleOpt {
+    /// Returns `true` if any of the options differs from the default.
+    pub fn is_customized(&self) -> bool {
+        self.no_auto_log_scale || !self.log_params.is_empty() || !self.linear_params.is_empty()
+    }
+
+    pub fn is_log_scale(&self, def: &ParamDef) -> bool {
+        match def.range {
+            ParamRange::Categorical { .. } => false,
+            _ if self.linear_params.contains(&def.name) => false,
+            _ if self.log_params.contains(&def.name) => true,
+            ParamRange::Numerical { scale, .. } => {
+                !self.no_auto_log_scale && matches!(scale, Scale::Log)
+            }
+        }
+    }
+
+    /// Converts a parameter value into the scale used in analyses.
+    ///
+    /// NaN values (i.e., inactive parameters) are returned as they are.
+    pub fn transform(&self, def: &ParamDef, value: f64) -> anyhow::Result<f64> {
+        if !self.is_log_scale(def) || value.is_nan() {
+            return Ok(value);
+        }
+
+        ensure!(
+            value > 0.0,
+            "cannot log-transform the non-positive value {} of the param {:?} \
+             (use `--linear-param {}` to disable the transformation)",
+            value,
+            def.name,
+            def.name
+        );
+        Ok(value.ln())
+    }
+}
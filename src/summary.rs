@@ -0,0 +1,221 @@
+use crate::io::InputOpt;
+use crate::utils::eval_state_name;
+use hporecord::{ParamRange, Record, StudyId, StudyRecord};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use structopt::StructOpt;
+
+pub type Summaries = BTreeMap<StudyId, StudySummary>;
+
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct SummaryOpt {
+    #[structopt(flatten)]
+    pub input: InputOpt,
+
+    /// Outputs the summary as JSON instead of human-readable tables.
+    #[structopt(long)]
+    pub json: bool,
+}
+
+impl SummaryOpt {
+    pub fn summarize<I>(&self, records: I) -> anyhow::Result<Summaries>
+    where
+        I: Iterator<Item = anyhow::Result<Record>>,
+    {
+        let mut summaries = BTreeMap::new();
+        let mut skipped_studies = BTreeSet::new();
+        for record in records {
+            match record? {
+                Record::Study(study) => {
+                    if !summaries.contains_key(&study.id) {
+                        summaries.insert(study.id.clone(), StudySummary::new(study));
+                    }
+                }
+                Record::Eval(eval) => {
+                    let summary = if let Some(summary) = summaries.get_mut(&eval.study) {
+                        summary
+                    } else {
+                        if !skipped_studies.contains(&eval.study) {
+                            eprintln!("[WARN] Unknown study: {:?}", eval.study);
+                            skipped_studies.insert(eval.study);
+                        }
+                        continue;
+                    };
+
+                    *summary
+                        .evals
+                        .entry(eval_state_name(&eval.state))
+                        .or_default() += 1;
+
+                    for (extent, span) in summary.span_extents.iter_mut().zip(eval.spans.iter()) {
+                        *extent = Some(extent.map_or(
+                            Extent {
+                                start: span.start,
+                                end: span.end,
+                            },
+                            |e| Extent {
+                                start: e.start.min(span.start),
+                                end: e.end.max(span.end),
+                            },
+                        ));
+                    }
+
+                    if !eval.state.is_complete() {
+                        continue;
+                    }
+                    for ((best, def), &value) in summary
+                        .best_values
+                        .iter_mut()
+                        .zip(summary.study.values.iter())
+                        .zip(eval.values.iter())
+                    {
+                        *best = Some(best.map_or(value, |b| def.direction.better(b, value)));
+                    }
+                }
+            }
+        }
+        Ok(summaries)
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W, summaries: &Summaries) -> anyhow::Result<()> {
+        if self.json {
+            serde_json::to_writer(&mut writer, summaries)?;
+            writeln!(writer)?;
+            return Ok(());
+        }
+
+        for (i, summary) in summaries.values().enumerate() {
+            if i > 0 {
+                writeln!(writer)?;
+            }
+            summary.write_tables(&mut writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StudySummary {
+    pub study: StudyRecord,
+    pub evals: BTreeMap<String, usize>,
+    pub best_values: Vec<Option<f64>>,
+    pub span_extents: Vec<Option<Extent>>,
+}
+
+impl StudySummary {
+    fn new(study: StudyRecord) -> Self {
+        Self {
+            evals: BTreeMap::new(),
+            best_values: vec![None; study.values.len()],
+            span_extents: vec![None; study.spans.len()],
+            study,
+        }
+    }
+
+    fn write_tables<W: Write>(&self, mut writer: W) -> anyhow::Result<()> {
+        writeln!(writer, "Study: {}", self.study.id)?;
+
+        let attrs = self
+            .study
+            .attrs
+            .iter()
+            .map(|(k, v)| vec![k.to_string(), v.to_string()])
+            .collect();
+        write_table(&mut writer, &["Attr", "Value"], attrs)?;
+
+        let params = self
+            .study
+            .params
+            .iter()
+            .map(|p| match &p.range {
+                ParamRange::Numerical {
+                    min, max, scale, ..
+                } => vec![
+                    p.name.clone(),
+                    format!("[{}, {}]", min, max),
+                    format!("{:?}", scale),
+                ],
+                ParamRange::Categorical { choices } => vec![
+                    p.name.clone(),
+                    format!("{{{}}}", choices.join(", ")),
+                    "-".to_owned(),
+                ],
+            })
+            .collect();
+        write_table(&mut writer, &["Param", "Range", "Scale"], params)?;
+
+        let values = self
+            .study
+            .values
+            .iter()
+            .zip(self.best_values.iter())
+            .map(|(v, best)| {
+                vec![
+                    v.name.clone(),
+                    format!("{:?}", v.direction),
+                    best.map_or_else(|| "-".to_owned(), |b| b.to_string()),
+                ]
+            })
+            .collect();
+        write_table(&mut writer, &["Value", "Direction", "Best"], values)?;
+
+        let spans = self
+            .study
+            .spans
+            .iter()
+            .zip(self.span_extents.iter())
+            .map(|(s, extent)| {
+                vec![
+                    s.name.clone(),
+                    extent.map_or_else(|| "-".to_owned(), |e| format!("[{}, {}]", e.start, e.end)),
+                ]
+            })
+            .collect();
+        write_table(&mut writer, &["Span", "Extent"], spans)?;
+
+        let evals = self
+            .evals
+            .iter()
+            .map(|(state, count)| vec![state.clone(), count.to_string()])
+            .collect();
+        write_table(&mut writer, &["State", "Evals"], evals)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Extent {
+    pub start: f64,
+    pub end: f64,
+}
+
+fn write_table<W: Write>(
+    mut writer: W,
+    headers: &[&str],
+    rows: Vec<Vec<String>>,
+) -> anyhow::Result<()> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let mut widths = headers.iter().map(|h| h.len()).collect::<Vec<_>>();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    writeln!(writer)?;
+    let headers = headers.iter().map(|h| h.to_string()).collect::<Vec<_>>();
+    for row in std::iter::once(&headers).chain(rows.iter()) {
+        let mut line = " ".to_owned();
+        for (cell, width) in row.iter().zip(widths.iter()) {
+            line += &format!(" {:width$}", cell, width = width);
+        }
+        writeln!(writer, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
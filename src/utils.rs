@@ -1,6 +1,5 @@
-use hporecord::Record;
+use hporecord::{EvalState, StudyRecord};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct MeanAndStddev<T = f64> {
@@ -8,24 +7,73 @@ pub struct MeanAndStddev<T = f64> {
     pub stddev: T,
 }
 
-pub fn build_id_mapping(
+impl MeanAndStddev {
+    /// Calculates the mean and the (population) standard deviation of `xs`.
+    pub fn from_samples(xs: &[f64]) -> Self {
+        let n = xs.len() as f64;
+        let mean = xs.iter().sum::<f64>() / n;
+        let stddev = (xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n).sqrt();
+        Self { mean, stddev }
+    }
+}
+
+/// Returns the `q`-quantile of ascendingly `sorted` values with linear interpolation.
+pub fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let x = q * (sorted.len() - 1) as f64;
+    let i = x.floor() as usize;
+    let j = x.ceil() as usize;
+    sorted[i] + (sorted[j] - sorted[i]) * (x - i as f64)
+}
+
+pub fn eval_study_script(
+    lua: &rlua::Lua,
     script: &str,
-    records: &[Record],
-) -> anyhow::Result<BTreeMap<String, String>> {
-    let mut id_mapping = BTreeMap::new();
-    for record in records {
-        if let Record::Study(study) = record {
-            let lua = rlua::Lua::new();
-            let new_id: String = lua.context(|lua_ctx| {
-                let globals = lua_ctx.globals();
-
-                // TODO
-                globals.set("attrs", study.attrs.clone())?;
-
-                lua_ctx.load(&script).eval()
-            })?;
-            id_mapping.insert(study.id.clone(), new_id);
+    study: &StudyRecord,
+) -> anyhow::Result<String> {
+    let value = lua.context(|lua_ctx| {
+        let globals = lua_ctx.globals();
+        globals.set("attrs", study.attrs.clone())?;
+
+        lua_ctx.load(script).eval()
+    })?;
+    Ok(value)
+}
+
+pub fn eval_state_name(state: &EvalState) -> String {
+    match serde_json::to_value(state) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => format!("{:?}", state),
+    }
+}
+
+/// Returns human-readable differences between two lists of definitions (e.g., `ParamDef`s).
+pub fn diff_defs<T: Serialize>(label: &str, a: &[T], b: &[T]) -> anyhow::Result<Vec<String>> {
+    let mut diffs = Vec::new();
+    if a.len() != b.len() {
+        diffs.push(format!(
+            "{}: the numbers of definitions differ ({} vs {})",
+            label,
+            a.len(),
+            b.len()
+        ));
+    }
+    for (i, (a, b)) in a.iter().zip(b.iter()).enumerate() {
+        let a = serde_json::to_string(a)?;
+        let b = serde_json::to_string(b)?;
+        if a != b {
+            diffs.push(format!("{}[{}]: {} vs {}", label, i, a, b));
         }
     }
-    Ok(id_mapping)
+    Ok(diffs)
+}
+
+/// Returns the differences between the param, value and span definitions of two studies.
+pub fn diff_study_defs(a: &StudyRecord, b: &StudyRecord) -> anyhow::Result<Vec<String>> {
+    let mut diffs = diff_defs("params", &a.params, &b.params)?;
+    diffs.extend(diff_defs("values", &a.values, &b.values)?);
+    diffs.extend(diff_defs("spans", &a.spans, &b.spans)?);
+    Ok(diffs)
 }
@@ -0,0 +1,297 @@
+use crate::io::InputOpt;
+use anyhow::bail;
+use hporecord::{EvalRecord, ParamRange, Record, Scale, StudyId, StudyRecord};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ValidateOpt {
+    #[structopt(flatten)]
+    pub input: InputOpt,
+}
+
+impl ValidateOpt {
+    pub fn validate<W: Write>(&self, mut writer: W) -> anyhow::Result<()> {
+        let mut records = self.input.read_records()?.unvalidated();
+        let mut validator = Validator::default();
+        let mut unknown_studies = BTreeSet::new();
+        let mut violations = 0;
+        let mut invalid_records = 0;
+        while let Some(record) = records.next() {
+            let record = record?;
+            let location = records.location().expect("unreachable");
+            if let Record::Eval(eval) = &record {
+                if !validator.is_known_study(&eval.study)
+                    && unknown_studies.insert(eval.study.clone())
+                {
+                    writeln!(writer, "{}: unknown study {:?}", location, eval.study)?;
+                    violations += 1;
+                    invalid_records += 1;
+                }
+            }
+
+            let messages = validator.validate(&record);
+            if !messages.is_empty() {
+                invalid_records += 1;
+            }
+            for message in messages {
+                writeln!(writer, "{}: {}", location, message)?;
+                violations += 1;
+            }
+        }
+        writer.flush()?;
+
+        if violations > 0 {
+            bail!(
+                "found {} violations in {} records",
+                violations,
+                invalid_records
+            );
+        }
+        eprintln!("No violations found");
+        Ok(())
+    }
+}
+
+/// Checks that evaluation records conform to the definitions of their studies.
+#[derive(Debug, Default)]
+pub struct Validator {
+    studies: BTreeMap<StudyId, StudyRecord>,
+}
+
+impl Validator {
+    pub fn is_known_study(&self, id: &StudyId) -> bool {
+        self.studies.contains_key(id)
+    }
+
+    /// Returns the violations found in the given record.
+    ///
+    /// Evaluations of unknown studies are not reported because each analysis handles them differently.
+    pub fn validate(&mut self, record: &Record) -> Vec<String> {
+        match record {
+            Record::Study(study) => {
+                let violations = validate_study(study);
+                if violations.is_empty() && !self.studies.contains_key(&study.id) {
+                    self.studies.insert(study.id.clone(), study.clone());
+                }
+                violations
+            }
+            Record::Eval(eval) => {
+                if let Some(study) = self.studies.get(&eval.study) {
+                    validate_eval(study, eval)
+                } else {
+                    Vec::new()
+                }
+            }
+        }
+    }
+}
+
+fn validate_study(study: &StudyRecord) -> Vec<String> {
+    let mut violations = Vec::new();
+    for def in &study.params {
+        match &def.range {
+            ParamRange::Numerical {
+                min, max, scale, ..
+            } => {
+                if min.is_nan() || max.is_nan() || min > max {
+                    violations.push(format!(
+                        "the range of the param {:?} is empty ([{}, {}])",
+                        def.name, min, max
+                    ));
+                }
+                if matches!(scale, Scale::Log) && *min <= 0.0 {
+                    violations.push(format!(
+                        "the param {:?} has the log scale but its range [{}, {}] is not positive",
+                        def.name, min, max
+                    ));
+                }
+            }
+            ParamRange::Categorical { choices } => {
+                if choices.is_empty() {
+                    violations.push(format!("the param {:?} has no choices", def.name));
+                }
+            }
+        }
+    }
+    violations
+}
+
+fn validate_eval(study: &StudyRecord, eval: &EvalRecord) -> Vec<String> {
+    let mut violations = Vec::new();
+
+    if eval.params.len() != study.params.len() {
+        violations.push(format!(
+            "expected {} params but got {}",
+            study.params.len(),
+            eval.params.len()
+        ));
+    }
+    for (def, &p) in study.params.iter().zip(eval.params.iter()) {
+        if p.is_nan() {
+            // An inactive parameter of a conditional search space.
+            continue;
+        }
+        match &def.range {
+            ParamRange::Numerical { min, max, .. } => {
+                let tolerance = 1e-9 * min.abs().max(max.abs()).max(1.0);
+                if !(min - tolerance <= p && p <= max + tolerance) {
+                    violations.push(format!(
+                        "the value {} of the param {:?} is out of the range [{}, {}]",
+                        p, def.name, min, max
+                    ));
+                }
+            }
+            ParamRange::Categorical { choices } => {
+                if !(p.fract() == 0.0 && 0.0 <= p && p < choices.len() as f64) {
+                    violations.push(format!(
+                        "the value {} of the param {:?} is not a valid index of {} choices",
+                        p,
+                        def.name,
+                        choices.len()
+                    ));
+                }
+            }
+        }
+    }
+
+    if (eval.state.is_complete() || !eval.values.is_empty())
+        && eval.values.len() != study.values.len()
+    {
+        violations.push(format!(
+            "expected {} values but got {}",
+            study.values.len(),
+            eval.values.len()
+        ));
+    }
+
+    if eval.spans.len() != study.spans.len() {
+        violations.push(format!(
+            "expected {} spans but got {}",
+            study.spans.len(),
+            eval.spans.len()
+        ));
+    }
+    for (def, span) in study.spans.iter().zip(eval.spans.iter()) {
+        if span.start.is_nan() || span.end.is_nan() || span.start > span.end {
+            violations.push(format!(
+                "the span {:?} is not monotonic (start={}, end={})",
+                def.name, span.start, span.end
+            ));
+        }
+    }
+
+    violations
+}
+
+/// Error returned for records that violate the definitions of their studies.
+#[derive(Debug)]
+pub struct InvalidRecord {
+    pub violations: Vec<String>,
+}
+
+impl std::fmt::Display for InvalidRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.violations.join("; "))
+    }
+}
+
+impl std::error::Error for InvalidRecord {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn study() -> StudyRecord {
+        serde_json::from_str(
+            r#"{
+              "id": "s",
+              "attrs": {},
+              "params": [
+                {"name": "x", "range": {"Numerical": {"min": 0.0, "max": 1.0, "step": null, "scale": "Linear"}}},
+                {"name": "c", "range": {"Categorical": {"choices": ["a", "b", "c"]}}}
+              ],
+              "values": [{"name": "loss", "direction": "Minimize"}],
+              "spans": [{"name": "time"}]
+            }"#,
+        )
+        .expect("invalid study")
+    }
+
+    fn eval(x: f64, c: f64, span: (f64, f64)) -> EvalRecord {
+        let mut eval: EvalRecord = serde_json::from_str(
+            r#"{
+              "study": "s",
+              "state": "Complete",
+              "params": [0.0, 0],
+              "values": [1.0],
+              "spans": [{"start": 0.0, "end": 1.0}]
+            }"#,
+        )
+        .expect("invalid eval");
+        eval.params = vec![x, c];
+        eval.spans[0].start = span.0;
+        eval.spans[0].end = span.1;
+        eval
+    }
+
+    #[test]
+    fn numerical_range_has_tolerance() {
+        let study = study();
+        assert!(validate_eval(&study, &eval(0.5, 0.0, (0.0, 1.0))).is_empty());
+        assert!(validate_eval(&study, &eval(-1e-12, 0.0, (0.0, 1.0))).is_empty());
+        assert!(validate_eval(&study, &eval(1.0 + 1e-12, 0.0, (0.0, 1.0))).is_empty());
+        assert_eq!(
+            validate_eval(&study, &eval(1.001, 0.0, (0.0, 1.0))).len(),
+            1
+        );
+        assert_eq!(
+            validate_eval(&study, &eval(-0.001, 0.0, (0.0, 1.0))).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn categorical_value_must_be_index() {
+        let study = study();
+        assert!(validate_eval(&study, &eval(0.5, 2.0, (0.0, 1.0))).is_empty());
+        for &c in &[3.0, 1.5, -1.0, f64::INFINITY] {
+            assert_eq!(validate_eval(&study, &eval(0.5, c, (0.0, 1.0))).len(), 1);
+        }
+    }
+
+    #[test]
+    fn nan_params_are_inactive() {
+        let study = study();
+        assert!(validate_eval(&study, &eval(f64::NAN, f64::NAN, (0.0, 1.0))).is_empty());
+    }
+
+    #[test]
+    fn span_must_be_monotonic() {
+        let study = study();
+        assert!(validate_eval(&study, &eval(0.5, 0.0, (1.0, 1.0))).is_empty());
+        assert_eq!(validate_eval(&study, &eval(0.5, 0.0, (2.0, 1.0))).len(), 1);
+        assert_eq!(
+            validate_eval(&study, &eval(0.5, 0.0, (0.0, f64::NAN))).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn evals_of_unknown_studies_are_not_reported() {
+        let mut validator = Validator::default();
+        assert!(validator
+            .validate(&Record::Eval(eval(2.0, 5.0, (1.0, 0.0))))
+            .is_empty());
+
+        assert!(validator.validate(&Record::Study(study())).is_empty());
+        assert_eq!(
+            validator
+                .validate(&Record::Eval(eval(2.0, 5.0, (1.0, 0.0))))
+                .len(),
+            3
+        );
+    }
+}